@@ -0,0 +1,59 @@
+//! Benchmarks of OBJ parsing.
+//! Compare against older revisions with `cargo bench -- --save-baseline <name>`
+//! and `cargo bench -- --baseline <name>`.
+
+use std::{fmt::Write, io::Cursor};
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use maenami::{ObjCommands, Parser};
+
+/// The number of vertices on each side of the grid.
+const GRID_SIZE: usize = 300;
+
+/// Generates a grid mesh with texture UVs and normals.
+fn grid_obj(size: usize) -> String {
+    let mut source = String::from("# grid\no grid\ng surface\ns 1\n");
+    for y in 0..size {
+        for x in 0..size {
+            let (u, v) = (x as f32 / size as f32, y as f32 / size as f32);
+            let height = (u * 12.0).sin() * (v * 7.0).cos();
+            writeln!(source, "v {:.6} {:.6} {:.6}", u, height, v).unwrap();
+            writeln!(source, "vt {:.6} {:.6}", u, v).unwrap();
+            writeln!(source, "vn {:.6} {:.6} {:.6}", 0.0, 1.0, 0.0).unwrap();
+        }
+    }
+    for y in 0..(size - 1) {
+        for x in 0..(size - 1) {
+            let i = y * size + x + 1;
+            let corners = [i, i + 1, i + size + 1, i + size];
+            source.push('f');
+            for c in corners {
+                write!(source, " {c}/{c}/{c}").unwrap();
+            }
+            source.push('\n');
+        }
+    }
+    source
+}
+
+fn parse(c: &mut Criterion) {
+    let source = grid_obj(GRID_SIZE);
+    let mut parser = Parser::new(|_, _: &()| Ok(Cursor::new(vec![])));
+
+    let mut group = c.benchmark_group("grid");
+    group.throughput(Throughput::Bytes(source.len() as u64));
+    group.sample_size(20);
+    group.bench_function("commands", |b| {
+        b.iter(|| ObjCommands::new(Cursor::new(source.as_bytes())).count())
+    });
+    group.bench_function("parse", |b| {
+        b.iter(|| parser.parse(Cursor::new(source.as_bytes()), ()).unwrap())
+    });
+    group.bench_function("parse_parallel", |b| {
+        b.iter(|| parser.parse_parallel(&source, ()).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
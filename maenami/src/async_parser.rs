@@ -0,0 +1,165 @@
+use crate::{
+    command::{is_continued, Decoded, MtlDecoder, ObjCommand, ObjDecoder},
+    diagnostic::Diagnostic,
+    mtl::Material,
+    parser::{MtlBuilder, ObjBuilder},
+    Result, WavefrontObj,
+};
+
+use std::{future::Future, io::Result as IoResult, path::Path};
+
+use futures_lite::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, BufReader};
+
+/// Represents the asynchronous parser of OBJ/MTL.
+/// Available with `async` feature.
+pub struct AsyncParser<F> {
+    include_function: F,
+    strict: bool,
+}
+
+impl<F> AsyncParser<F> {
+    /// Creates an instance of `AsyncParser`.
+    /// # Parameters
+    /// * `include_function`
+    ///     - An asynchronous resolver closure/function for MTL file
+    ///     - When detects `mtllib` command, it tries to resolve the path of
+    ///       MTL file. The parser calls this resolver with detected path and context object,
+    ///       so you can return a `Future` resolving into any `AsyncRead` instance or error.
+    ///       The `Future` cannot borrow the arguments, so clone them if needed.
+    pub fn new(include_function: F) -> AsyncParser<F> {
+        AsyncParser {
+            include_function,
+            strict: false,
+        }
+    }
+
+    /// Sets whether to treat diagnostics as errors.
+    /// See `Parser::strict` for details.
+    pub fn strict(mut self, strict: bool) -> AsyncParser<F> {
+        self.strict = strict;
+        self
+    }
+
+    /// Parses the OBJ file.
+    pub async fn parse<C, R, Fut>(
+        &mut self,
+        mut reader: impl AsyncBufRead + Unpin,
+        context: C,
+    ) -> Result<WavefrontObj>
+    where
+        F: FnMut(&Path, &C) -> Fut,
+        Fut: Future<Output = Result<R>>,
+        R: AsyncRead + Unpin,
+    {
+        let mut decoder = ObjDecoder::default();
+        let mut builder = ObjBuilder::new(self.strict);
+        let mut line_buffer = String::with_capacity(1024);
+        loop {
+            line_buffer.clear();
+            let read_size = read_logical_line(&mut reader, &mut line_buffer)
+                .await
+                .map_err(|e| decoder.read_error(e))?;
+            if read_size == 0 {
+                break;
+            }
+
+            match decoder.decode_line(&line_buffer)? {
+                Some(Decoded::Face) => builder.push_face(decoder.face()),
+                Some(Decoded::Command(ObjCommand::MaterialLibrary(paths))) => {
+                    for path in paths.iter() {
+                        let mtl_reader = (self.include_function)(path, &context)
+                            .await
+                            .map_err(|e| e.at(decoder.location(&line_buffer)))?;
+                        let (materials, diagnostics) =
+                            parse_mtl(mtl_reader, path, self.strict).await?;
+                        builder.add_materials(materials, diagnostics);
+                    }
+                }
+                Some(Decoded::Command(command)) => {
+                    builder.push(command, || decoder.location(&line_buffer))?
+                }
+                None => (),
+            }
+        }
+
+        Ok(builder.finish())
+    }
+}
+
+/// Parses MTL file.
+/// The reader will be wrapped with `BufReader`, so you don't have to
+/// do so.
+async fn parse_mtl(
+    reader: impl AsyncRead + Unpin,
+    path: &Path,
+    strict: bool,
+) -> Result<(Box<[Material]>, Vec<Diagnostic>)> {
+    let mut reader = BufReader::new(reader);
+    let mut decoder = MtlDecoder::new(path);
+    let mut builder = MtlBuilder::new(strict);
+    let mut line_buffer = String::with_capacity(1024);
+    loop {
+        line_buffer.clear();
+        let read_size = read_logical_line(&mut reader, &mut line_buffer)
+            .await
+            .map_err(|e| decoder.read_error(e))?;
+        if read_size == 0 {
+            break;
+        }
+
+        if let Some(command) = decoder.decode(&line_buffer)? {
+            builder.push(command, || decoder.location(&line_buffer))?;
+        }
+    }
+
+    Ok(builder.finish())
+}
+
+/// Reads a line, and following lines while the line ends with `\\`.
+/// Returns the number of bytes read.
+async fn read_logical_line(
+    reader: &mut (impl AsyncBufRead + Unpin),
+    buffer: &mut String,
+) -> IoResult<usize> {
+    // `read_line` of futures-lite requires an empty buffer
+    let mut line = String::new();
+    let mut total = 0;
+    loop {
+        line.clear();
+        let size = reader.read_line(&mut line).await?;
+        total += size;
+        buffer.push_str(&line);
+        if size == 0 || !is_continued(&line) {
+            return Ok(total);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures_lite::future::{block_on, ready};
+
+    fn parse(source: &str) -> Result<WavefrontObj> {
+        let mut parser = AsyncParser::new(|_: &Path, _: &()| ready(Ok(&b""[..])));
+        block_on(parser.parse(source.as_bytes(), ()))
+    }
+
+    #[test]
+    fn parses_same_as_parser() {
+        let source = "o a\nv 0 0 0 \\\n\nv 1 0 \\\n 0\n# c \\\nv 0 1 0\nf 1 2 3\n";
+        let expected = crate::Parser::new(|_, _: &()| Ok(&b""[..]))
+            .parse(source.as_bytes(), ())
+            .unwrap();
+        let parsed = parse(source).unwrap();
+        assert_eq!(format!("{:?}", parsed), format!("{:?}", expected));
+        assert_eq!(parsed.objects()[0].groups()[0].vertices().len(), 3);
+    }
+
+    #[test]
+    fn errors_have_line_numbers() {
+        let error = parse("v 0 0 0 \\\n\nf 1 2 3\n").unwrap_err();
+        assert_eq!(error.location().unwrap().line(), 3);
+    }
+}
@@ -0,0 +1,102 @@
+//! Contains application arguments definitions.
+
+use std::path::PathBuf;
+
+use clap::Parser;
+
+/// Inspects, validates and converts OBJ files.
+#[derive(Debug, Clone, Parser)]
+#[clap(author, version, about)]
+pub struct Arguments {
+    /// Subcommand to execute.
+    #[clap(subcommand)]
+    pub subcommand: Subcommand,
+}
+
+/// Subcommand definition.
+#[derive(Debug, Clone, Parser)]
+pub enum Subcommand {
+    /// Shows objects, groups, vertex data and materials.
+    Info(InfoArguments),
+
+    /// Validates the file and reports problems as JSON.
+    Validate(ValidateArguments),
+
+    /// Converts into OBJ, glTF, GLB or PLY.
+    Convert(ConvertArguments),
+}
+
+/// Arguments set for `Info`.
+#[derive(Debug, Clone, Parser)]
+pub struct InfoArguments {
+    /// OBJ, STL or PLY file to inspect.
+    pub input: PathBuf,
+}
+
+/// Arguments set for `Validate`.
+#[derive(Debug, Clone, Parser)]
+pub struct ValidateArguments {
+    /// OBJ, STL or PLY file to validate.
+    pub input: PathBuf,
+
+    /// Treats warnings as errors.
+    #[clap(short, long)]
+    pub strict: bool,
+}
+
+/// Arguments set for `Convert`.
+#[derive(Debug, Clone, Parser)]
+pub struct ConvertArguments {
+    /// OBJ, STL or PLY file to convert.
+    pub input: PathBuf,
+
+    /// Output file. The format is determined by the extension
+    /// (`obj`, `gltf`, `glb` or `ply`).
+    pub output: PathBuf,
+
+    /// Repairs the mesh before writing.
+    #[clap(short, long)]
+    pub repair: bool,
+
+    /// Welds vertices within the distance in repairing.
+    #[clap(short, long, requires = "repair")]
+    pub weld: Option<f32>,
+
+    /// Generates missing normals.
+    #[clap(short = 'n', long)]
+    pub generate_normals: bool,
+
+    /// Simplifies faces into the ratio of triangles, greater than 0 and at most 1.
+    #[clap(short, long, parse(try_from_str = ratio))]
+    pub decimate: Option<f32>,
+}
+
+/// Parses the ratio in `(0, 1]`.
+fn ratio(value: &str) -> Result<f32, String> {
+    let ratio = value.parse::<f32>().map_err(|e| e.to_string())?;
+    if ratio > 0.0 && ratio <= 1.0 {
+        Ok(ratio)
+    } else {
+        Err(format!("{} is not in (0, 1]", value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decimate_ratio_is_validated() {
+        let parse = |ratio: &str| {
+            Arguments::try_parse_from(["maenami", "convert", "a.obj", "b.obj", "-d", ratio])
+        };
+        match parse("0.5").unwrap().subcommand {
+            Subcommand::Convert(args) => assert_eq!(args.decimate, Some(0.5)),
+            _ => unreachable!(),
+        }
+        assert!(parse("1").is_ok());
+        for invalid in ["0", "-0.5", "1.5", "NaN", "half"] {
+            assert!(parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
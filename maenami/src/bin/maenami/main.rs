@@ -0,0 +1,28 @@
+mod app;
+mod subcommand;
+
+use crate::{
+    app::{Arguments, Subcommand},
+    subcommand::{convert, info, validate},
+};
+
+use std::process::ExitCode;
+
+use anyhow::Result;
+use clap::Parser;
+use flexi_logger::{colored_detailed_format, Logger};
+
+fn main() -> Result<ExitCode> {
+    Logger::try_with_env()?
+        .format_for_stderr(colored_detailed_format)
+        .start()?;
+
+    let args = Arguments::parse();
+    match args.subcommand {
+        Subcommand::Info(args) => info::run(args)?,
+        Subcommand::Validate(args) => return validate::run(args),
+        Subcommand::Convert(args) => convert::run(args)?,
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
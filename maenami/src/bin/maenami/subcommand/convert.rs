@@ -0,0 +1,185 @@
+//! Subcommand `convert`
+//!
+//! Converts into OBJ (normalizing the content), glTF, GLB or PLY.
+//! Relative texture paths are rewritten to be relative to the output file.
+
+use crate::{
+    app::ConvertArguments,
+    subcommand::{extension, load},
+};
+
+use std::{
+    fs::{canonicalize, File},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{bail, Result};
+use log::info;
+use maenami::{RepairOptions, WavefrontObj};
+
+pub fn run(args: ConvertArguments) -> Result<()> {
+    let mut obj = load(&args.input)?;
+    if args.repair {
+        obj = obj.repair(RepairOptions::new().weld(args.weld));
+    }
+    if args.generate_normals {
+        obj = obj.generate_normals();
+    }
+    if let Some(ratio) = args.decimate {
+        obj = obj.decimate(ratio);
+    }
+
+    let output = &args.output;
+    let input_directory = canonicalize(directory(&args.input))?;
+    let output_directory = canonicalize(directory(output))?;
+    if input_directory != output_directory {
+        obj = obj.map_paths(|p| rebase(p, &input_directory, &output_directory));
+    }
+
+    write(&obj, output)?;
+
+    info!(
+        "Converted {} into {}",
+        args.input.display(),
+        output.display()
+    );
+    Ok(())
+}
+
+/// Writes in the format determined by the extension of `output`.
+/// OBJ is written with MTL, and glTF is written with its binary buffer, in the same directory.
+fn write(obj: &WavefrontObj, output: &Path) -> Result<()> {
+    match extension(output).as_str() {
+        "obj" => {
+            if obj.materials().is_empty() {
+                obj.write_obj(File::create(output)?, None)?;
+            } else {
+                let mtl_path = output.with_extension("mtl");
+                let mtllib = sibling_name(&mtl_path);
+                obj.write_obj(File::create(output)?, Some(Path::new(&mtllib)))?;
+                obj.write_mtl(File::create(&mtl_path)?)?;
+            }
+        }
+        "gltf" => {
+            let buffer_path = output.with_extension("bin");
+            let buffer_name = sibling_name(&buffer_path);
+            obj.write_gltf(
+                File::create(output)?,
+                File::create(&buffer_path)?,
+                Path::new(&buffer_name),
+            )?;
+        }
+        "glb" => obj.write_glb(File::create(output)?)?,
+        "ply" => obj.write_ply(File::create(output)?)?,
+        other => bail!("Unsupported output format: \"{}\"", other),
+    }
+    Ok(())
+}
+
+/// The file name referred from the output file in the same directory.
+fn sibling_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// The directory containing the file.
+fn directory(path: &Path) -> &Path {
+    match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    }
+}
+
+/// Rewrites the path relative to `from` into the path relative to `to`.
+/// Both directories must be absolute. Absolute paths are kept as they are.
+fn rebase(path: &Path, from: &Path, to: &Path) -> PathBuf {
+    if path.has_root() {
+        return path.to_path_buf();
+    }
+
+    let joined = from.join(path);
+    let mut target = vec![];
+    for component in joined.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                if let Some(Component::Normal(_)) = target.last() {
+                    target.pop();
+                }
+            }
+            c => target.push(c),
+        }
+    }
+    let base: Vec<_> = to.components().collect();
+    let common = target
+        .iter()
+        .zip(base.iter())
+        .take_while(|(t, b)| t == b)
+        .count();
+    // Paths on different drives cannot be relative
+    if common == 0 {
+        return target.iter().collect();
+    }
+
+    let parents = base[common..].iter().map(|_| Component::ParentDir);
+    parents.chain(target[common..].iter().copied()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{env::temp_dir, fs, process};
+
+    use maenami::Parser;
+
+    #[test]
+    fn relative_paths_are_rebased() {
+        let (from, to) = (Path::new("/models/cube"), Path::new("/out/lod"));
+        let rebased = |path: &str| rebase(Path::new(path), from, to);
+        assert_eq!(
+            rebased("tex/a.png"),
+            Path::new("../../models/cube/tex/a.png")
+        );
+        assert_eq!(rebased("./../a.png"), Path::new("../../models/a.png"));
+        assert_eq!(rebased("/textures/a.png"), Path::new("/textures/a.png"));
+        let nested = rebase(Path::new("a.png"), from, &from.join("out"));
+        assert_eq!(nested, Path::new("../a.png"));
+    }
+
+    #[test]
+    fn output_format_is_determined_by_extension() {
+        let directory = temp_dir().join(format!("maenami-convert-{}", process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let mut parser = Parser::new(|_, _: &()| Ok(&b"newmtl red\nKd 1 0 0\n"[..]));
+        let source = "mtllib a.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl red\nf 1 2 3\n";
+        let obj = parser.parse(source.as_bytes(), ()).unwrap();
+
+        write(&obj, &directory.join("model.OBJ")).unwrap();
+        let written = fs::read_to_string(directory.join("model.OBJ")).unwrap();
+        assert!(written.starts_with("mtllib model.mtl\n"));
+        let mtl = fs::read_to_string(directory.join("model.mtl")).unwrap();
+        assert!(mtl.starts_with("newmtl red\n"));
+
+        write(&obj, &directory.join("model.gltf")).unwrap();
+        let gltf = fs::read_to_string(directory.join("model.gltf")).unwrap();
+        assert!(gltf.contains("\"uri\":\"model.bin\""));
+        assert!(directory.join("model.bin").is_file());
+
+        write(&obj, &directory.join("model.glb")).unwrap();
+        assert!(fs::read(directory.join("model.glb"))
+            .unwrap()
+            .starts_with(b"glTF"));
+
+        write(&obj, &directory.join("model.ply")).unwrap();
+        let ply = WavefrontObj::read_ply(File::open(directory.join("model.ply")).unwrap());
+        assert_eq!(ply.unwrap().objects()[0].groups()[0].vertices().len(), 3);
+
+        let error = write(&obj, &directory.join("model.fbx")).unwrap_err();
+        assert_eq!(error.to_string(), "Unsupported output format: \"fbx\"");
+        assert!(!directory.join("model.fbx").exists());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,128 @@
+//! Subcommand `info`
+//!
+//! Shows what maenami made of the file.
+
+use crate::{app::InfoArguments, subcommand::load};
+
+use anyhow::Result;
+use maenami::{MaterialProperty, WavefrontObj};
+
+pub fn run(args: InfoArguments) -> Result<()> {
+    let obj = load(&args.input)?;
+    println!("File: {}", args.input.display());
+    print_summary(&obj);
+    print_objects(&obj);
+    print_materials(&obj);
+
+    if !obj.diagnostics().is_empty() {
+        println!("Diagnostics:");
+        for diagnostic in obj.diagnostics() {
+            println!("  {}", diagnostic);
+        }
+    }
+
+    Ok(())
+}
+
+fn print_summary(obj: &WavefrontObj) {
+    let groups: Vec<_> = obj.objects().iter().flat_map(|o| o.groups()).collect();
+    let faces: usize = groups.iter().map(|g| g.face_index_pairs().len()).sum();
+    let triangles: usize = groups.iter().map(|g| triangle_count(g)).sum();
+    let lines: usize = groups.iter().map(|g| g.line_index_pairs().len()).sum();
+    let points: usize = groups.iter().map(|g| g.point_index_pairs().len()).sum();
+
+    println!(
+        "Objects: {}, Groups: {}, Materials: {}",
+        obj.objects().len(),
+        groups.len(),
+        obj.materials().len()
+    );
+    println!(
+        "Faces: {} ({} triangles), Lines: {}, Points: {}",
+        faces, triangles, lines, points
+    );
+    if let Some(group) = groups.first() {
+        println!(
+            "Vertices: {}, Texture UVs: {}, Normals: {}, Colors: {}",
+            group.vertices().len(),
+            group.texture_uvs().len(),
+            group.normals().len(),
+            if group.colors().is_some() {
+                "yes"
+            } else {
+                "no"
+            }
+        );
+    }
+    if let Some(bounds) = obj.bounds() {
+        println!(
+            "Bounds: ({}, {}, {}) - ({}, {}, {})",
+            bounds.min.x, bounds.min.y, bounds.min.z, bounds.max.x, bounds.max.y, bounds.max.z
+        );
+    }
+}
+
+fn print_objects(obj: &WavefrontObj) {
+    for (i, object) in obj.objects().iter().enumerate() {
+        println!("Object #{} {}", i, name(object.name()));
+        for (j, group) in object.groups().iter().enumerate() {
+            let mut materials: Vec<_> =
+                group.face_index_pairs().iter().map(|(_, m, _)| m).collect();
+            materials.sort_unstable();
+            materials.dedup();
+            let materials: Vec<_> = materials
+                .into_iter()
+                .map(|m| name(m.and_then(|m| obj.materials().get(m)).map(|m| m.name())))
+                .collect();
+
+            println!(
+                "  Group #{} {}: {} faces ({} triangles), {} lines, {} points, materials: {}",
+                j,
+                name(group.name()),
+                group.face_index_pairs().len(),
+                triangle_count(group),
+                group.line_index_pairs().len(),
+                group.point_index_pairs().len(),
+                materials.join(", ")
+            );
+        }
+    }
+}
+
+fn print_materials(obj: &WavefrontObj) {
+    for material in obj.materials() {
+        println!("Material {}", name(Some(material.name())));
+        let mut properties: Vec<_> = material.properties().collect();
+        properties.sort_by_key(|(key, _)| *key);
+        for (key, property) in properties {
+            let value = match property {
+                MaterialProperty::Float(v) => v.to_string(),
+                MaterialProperty::Integer(v) => v.to_string(),
+                MaterialProperty::Vector(v) => format!("{} {} {}", v.x, v.y, v.z),
+                MaterialProperty::Spectral(path, factor) => {
+                    format!("spectral {} {}", path.display(), factor)
+                }
+                MaterialProperty::Xyz(v) => format!("xyz {} {} {}", v.x, v.y, v.z),
+                MaterialProperty::Dissolve(v, false) => v.to_string(),
+                MaterialProperty::Dissolve(v, true) => format!("-halo {}", v),
+                MaterialProperty::Texture(t) => t.path().display().to_string(),
+            };
+            println!("  {} {}", key, value);
+        }
+    }
+}
+
+fn triangle_count(group: &maenami::Group) -> usize {
+    group
+        .face_index_pairs()
+        .iter()
+        .map(|(f, _, _)| f.len().saturating_sub(2))
+        .sum()
+}
+
+fn name(name: Option<&str>) -> String {
+    match name {
+        Some(name) => format!("\"{}\"", name),
+        None => "(unnamed)".into(),
+    }
+}
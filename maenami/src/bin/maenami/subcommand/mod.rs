@@ -0,0 +1,29 @@
+//! Contains all subcommand processes.
+
+pub mod convert;
+pub mod info;
+pub mod validate;
+
+use std::{fs::File, path::Path};
+
+use maenami::{FileResolver, Parser, Result, WavefrontObj};
+
+/// Loads OBJ, STL or PLY file, determining the format by the extension.
+/// MTL files are resolved relative to the OBJ file.
+pub fn load(path: &Path) -> Result<WavefrontObj> {
+    match extension(path).as_str() {
+        "stl" => WavefrontObj::read_stl(File::open(path)?),
+        "ply" => WavefrontObj::read_ply(File::open(path)?),
+        _ => {
+            let mut parser = Parser::with_resolver(FileResolver::for_obj(path));
+            parser.parse(File::open(path)?, ())
+        }
+    }
+}
+
+/// The lowercase extension of the path.
+pub fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
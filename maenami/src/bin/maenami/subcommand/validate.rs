@@ -0,0 +1,209 @@
+//! Subcommand `validate`
+//!
+//! Reports parse diagnostics and mesh problems as JSON.
+//! Exits with non-zero code if errors are found.
+
+use crate::{app::ValidateArguments, subcommand::load};
+
+use std::{path::Path, process::ExitCode};
+
+use anyhow::Result;
+use maenami::{
+    Diagnostic, DiagnosticKind, Error as MaenamiError, Result as MaenamiResult, ValidationIssue,
+    WavefrontObj,
+};
+use serde_json::{json, Value};
+
+pub fn run(args: ValidateArguments) -> Result<ExitCode> {
+    let (report, exit_code) = report(&args.input, load(&args.input), args.strict);
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(exit_code)
+}
+
+/// Builds the report of the loaded file, and the exit code.
+fn report(file: &Path, loaded: MaenamiResult<WavefrontObj>, strict: bool) -> (Value, ExitCode) {
+    let obj = match loaded {
+        Ok(obj) => obj,
+        Err(error) => {
+            let report = json!({
+                "file": file.display().to_string(),
+                "valid": false,
+                "error": error_json(&error),
+            });
+            return (report, ExitCode::FAILURE);
+        }
+    };
+
+    let mut reports: Vec<_> = obj.diagnostics().iter().map(diagnostic_json).collect();
+    reports.extend(obj.validate().iter().map(issue_json));
+    let count = |severity: &str| reports.iter().filter(|r| r["severity"] == severity).count();
+    let errors = count("error");
+    let warnings = count("warning");
+    let valid = errors == 0 && !(strict && warnings > 0);
+
+    let report = json!({
+        "file": file.display().to_string(),
+        "valid": valid,
+        "errors": errors,
+        "warnings": warnings,
+        "diagnostics": reports,
+    });
+    let exit_code = if valid {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    };
+    (report, exit_code)
+}
+
+fn error_json(error: &MaenamiError) -> Value {
+    let mut value = json!({ "message": error.to_string() });
+    if let Some(location) = error.location() {
+        value["location"] = json!({
+            "file": location.file().to_string(),
+            "line": location.line(),
+            "text": location.text(),
+        });
+    }
+    value
+}
+
+fn diagnostic_json(diagnostic: &Diagnostic) -> Value {
+    let kind = match diagnostic.kind() {
+        DiagnosticKind::UnknownKeyword(_) => "unknown_keyword",
+        DiagnosticKind::UnknownMaterial(_) => "unknown_material",
+        DiagnosticKind::UnknownTextureOption(_) => "unknown_texture_option",
+        _ => "other",
+    };
+    let location = diagnostic.location();
+    json!({
+        "severity": "warning",
+        "kind": kind,
+        "message": diagnostic.kind().to_string(),
+        "location": {
+            "file": location.file().to_string(),
+            "line": location.line(),
+            "text": location.text(),
+        },
+    })
+}
+
+fn issue_json(issue: &ValidationIssue) -> Value {
+    let (severity, mut value) = match issue {
+        ValidationIssue::TooFewVertices {
+            object,
+            group,
+            face,
+        } => (
+            "error",
+            json!({ "kind": "too_few_vertices", "object": object, "group": group, "face": face }),
+        ),
+        ValidationIssue::DegenerateFace {
+            object,
+            group,
+            face,
+        } => (
+            "error",
+            json!({ "kind": "degenerate_face", "object": object, "group": group, "face": face }),
+        ),
+        ValidationIssue::NonManifoldEdge {
+            object,
+            vertices,
+            faces,
+        } => (
+            "error",
+            json!({ "kind": "non_manifold_edge", "object": object, "vertices": vertices, "faces": faces }),
+        ),
+        ValidationIssue::BoundaryEdge { object, vertices } => (
+            "warning",
+            json!({ "kind": "boundary_edge", "object": object, "vertices": vertices }),
+        ),
+        ValidationIssue::UnusedVertex(i) => {
+            ("warning", json!({ "kind": "unused_vertex", "vertex": i }))
+        }
+        ValidationIssue::NonFiniteVertex(i) => {
+            ("error", json!({ "kind": "non_finite_vertex", "vertex": i }))
+        }
+        ValidationIssue::InvalidNormal(i) => {
+            ("error", json!({ "kind": "invalid_normal", "normal": i }))
+        }
+        ValidationIssue::UvOutOfRange(i) => (
+            "warning",
+            json!({ "kind": "uv_out_of_range", "texture_uv": i }),
+        ),
+        _ => ("warning", json!({ "kind": "other" })),
+    };
+    value["severity"] = json!(severity);
+    value["message"] = json!(issue.to_string());
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use maenami::Parser;
+
+    fn validate(source: &str, strict: bool) -> (Value, ExitCode) {
+        let mut parser = Parser::new(|_, _: &()| Ok(Cursor::new(vec![])));
+        let loaded = parser.parse(source.as_bytes(), ());
+        report(Path::new("model.obj"), loaded, strict)
+    }
+
+    const TETRAHEDRON: &str = "\
+v 0 0 0
+v 1 0 0
+v 0 1 0
+v 0 0 1
+f 1 3 2
+f 1 2 4
+f 2 3 4
+f 1 4 3
+";
+
+    #[test]
+    fn valid_obj_is_reported() {
+        let (report, exit_code) = validate(TETRAHEDRON, true);
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(
+            report,
+            json!({
+                "file": "model.obj",
+                "valid": true,
+                "errors": 0,
+                "warnings": 0,
+                "diagnostics": [],
+            })
+        );
+    }
+
+    #[test]
+    fn load_error_is_reported_with_location() {
+        let (report, exit_code) = validate("v 0 0 0\nv 1 0\n", false);
+        assert_eq!(exit_code, ExitCode::FAILURE);
+        assert_eq!(report["valid"], false);
+        assert_eq!(report["error"]["location"]["line"], 2);
+        assert_eq!(report["error"]["location"]["text"], "v 1 0");
+        assert!(report.get("diagnostics").is_none());
+    }
+
+    #[test]
+    fn warnings_fail_only_in_strict_mode() {
+        let source = format!("vendor_keyword\n{}", TETRAHEDRON);
+        let (report, exit_code) = validate(&source, false);
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(report["valid"], true);
+        assert_eq!(report["warnings"], 1);
+        let diagnostic = &report["diagnostics"][0];
+        assert_eq!(diagnostic["severity"], "warning");
+        assert_eq!(diagnostic["kind"], "unknown_keyword");
+        assert_eq!(diagnostic["location"]["line"], 1);
+
+        let (report, exit_code) = validate(&source, true);
+        assert_eq!(exit_code, ExitCode::FAILURE);
+        assert_eq!(report["valid"], false);
+        assert_eq!(report["errors"], 0);
+    }
+}
@@ -0,0 +1,1238 @@
+use crate::{
+    mtl::{TextureChannel, TextureMap},
+    obj::FaceIndexPair,
+    Error, Location, Result, SourceFile,
+};
+
+use std::{
+    borrow::Cow,
+    io::{prelude::*, Error as IoError, Result as IoResult},
+    iter::from_fn,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use vek::{Rgb, Vec2, Vec3};
+
+/// Represents the abstract data of a line in OBJ file.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum ObjCommand {
+    /// `mtllib`, with one or more files
+    MaterialLibrary(Box<[Box<Path>]>),
+
+    /// `usemtl`, with the whole name which may contain spaces
+    UseMaterial(Box<str>),
+
+    /// `o`, with the whole name which may contain spaces
+    Object(Option<Box<str>>),
+
+    /// `g`, with group names separated by spaces
+    Group(Box<[Box<str>]>),
+
+    /// `v`, with optional weight `w` and vertex color
+    /// (`v x y z [w]` or `v x y z [w] r g b`)
+    Vertex(Vec3<f32>, Option<f32>, Option<Rgb<f32>>),
+
+    /// `vt`, with optional `w`
+    /// `v` is 0 if omitted.
+    VertexUv(Vec2<f32>, Option<f32>),
+
+    /// `vn`
+    VertexNormal(Vec3<f32>),
+
+    /// `vp`
+    /// `v` is 0 and `w` is 1 if omitted.
+    ParameterVertex(Vec3<f32>),
+
+    /// `f`
+    /// Indices are resolved into 0-based indices counted in whole file,
+    /// and checked against the elements defined so far.
+    Face(Box<[FaceIndexPair]>),
+
+    /// `l`
+    /// Indices are resolved and checked like `Face`. At least 2 vertices are required.
+    Line(Box<[FaceIndexPair]>),
+
+    /// `p`
+    /// Indices are resolved and checked like `Face`. At least 1 vertex is required.
+    Points(Box<[FaceIndexPair]>),
+
+    /// `s`
+    SmoothingGroup(Option<u32>),
+
+    /// Any other unknown keyword and its arguments
+    Unknown(Box<str>, Box<[Box<str>]>),
+}
+
+/// Represents the abstract data of a line in MTL file.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum MtlCommand {
+    /// `newmtl`, with the whole name which may contain spaces
+    NewMaterial(Box<str>),
+
+    /// Integer property
+    Integer(Box<str>, u32),
+
+    /// Float property
+    Float(Box<str>, f32),
+
+    /// Vector property
+    Vector(Box<str>, Vec3<f32>),
+
+    /// Spectral curve property
+    Spectral(Box<str>, Box<Path>, f32),
+
+    /// CIE XYZ property
+    Xyz(Box<str>, Vec3<f32>),
+
+    /// `d`, and whether `-halo` is specified
+    Dissolve(f32, bool),
+
+    /// Texture map property, and unknown options skipped in it
+    Texture(Box<str>, TextureMap, Box<[Box<str>]>),
+
+    /// Any other unknown keyword and its arguments
+    Unknown(Box<str>, Box<[Box<str>]>),
+}
+
+/// The iterator of commands in OBJ file.
+/// Empty lines and comments are skipped, and lines ending with `\\` are joined.
+pub struct ObjCommands<R> {
+    reader: R,
+    line_buffer: String,
+    decoder: ObjDecoder,
+}
+
+impl<R: BufRead> ObjCommands<R> {
+    /// Creates an instance of `ObjCommands`.
+    pub fn new(reader: R) -> ObjCommands<R> {
+        ObjCommands {
+            reader,
+            line_buffer: String::with_capacity(1024),
+            decoder: ObjDecoder::default(),
+        }
+    }
+
+    /// The location of the line last read.
+    pub fn location(&self) -> Location {
+        self.decoder.location(&self.line_buffer)
+    }
+
+    /// Reads the next command, keeping faces in the decoder.
+    pub(crate) fn next_decoded(&mut self) -> Option<Result<Decoded>> {
+        loop {
+            self.line_buffer.clear();
+            match read_logical_line(&mut self.reader, &mut self.line_buffer) {
+                Ok(0) => return None,
+                Ok(_) => (),
+                Err(e) => return Some(Err(self.decoder.read_error(e))),
+            }
+
+            if let Some(result) = self.decoder.decode_line(&self.line_buffer).transpose() {
+                return Some(result);
+            }
+        }
+    }
+
+    /// The face last decoded.
+    pub(crate) fn face(&self) -> &[FaceIndexPair] {
+        self.decoder.face()
+    }
+}
+
+impl<R: BufRead> Iterator for ObjCommands<R> {
+    type Item = Result<ObjCommand>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let decoded = self.next_decoded()?;
+        Some(decoded.map(|d| self.decoder.command(d)))
+    }
+}
+
+/// The iterator of commands in MTL file.
+/// Empty lines and comments are skipped, and lines ending with `\\` are joined.
+pub struct MtlCommands<R> {
+    reader: R,
+    line_buffer: String,
+    decoder: MtlDecoder,
+}
+
+impl<R: BufRead> MtlCommands<R> {
+    /// Creates an instance of `MtlCommands`.
+    /// `path` is used for the location of errors.
+    pub fn new(reader: R, path: &Path) -> MtlCommands<R> {
+        MtlCommands {
+            reader,
+            line_buffer: String::with_capacity(1024),
+            decoder: MtlDecoder::new(path),
+        }
+    }
+
+    /// The location of the line last read.
+    pub fn location(&self) -> Location {
+        self.decoder.location(&self.line_buffer)
+    }
+}
+
+impl<R: BufRead> Iterator for MtlCommands<R> {
+    type Item = Result<MtlCommand>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.line_buffer.clear();
+            match read_logical_line(&mut self.reader, &mut self.line_buffer) {
+                Ok(0) => return None,
+                Ok(_) => (),
+                Err(e) => return Some(Err(self.decoder.read_error(e))),
+            }
+
+            if let Some(result) = self.decoder.decode(&self.line_buffer).transpose() {
+                return Some(result);
+            }
+        }
+    }
+}
+
+/// Represents a decoded line of OBJ file.
+/// Faces are kept in the decoder (see `ObjDecoder::face`), so that decoding
+/// them doesn't allocate memory for each face.
+#[derive(Debug)]
+pub(crate) enum Decoded {
+    /// `f`
+    Face,
+
+    /// Any other command
+    Command(ObjCommand),
+}
+
+/// Decodes lines of OBJ file into commands.
+/// It tracks the line number and the number of defined elements.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ObjDecoder {
+    line_number: usize,
+    consumed_lines: usize,
+    vertices: usize,
+    uvs: usize,
+    normals: usize,
+    face_buffer: Vec<FaceIndexPair>,
+}
+
+impl ObjDecoder {
+    /// Decodes the next line.
+    /// The line may consist of lines continued with `\\`.
+    /// Returns `None` for empty lines and comments.
+    pub(crate) fn decode_line(&mut self, line: &str) -> Result<Option<Decoded>> {
+        let (line, count) = join_continued_lines(line);
+        self.line_number = self.consumed_lines + 1;
+        self.consumed_lines += count;
+        let line = &line[..];
+        let mut tokens = match tokenize(line) {
+            Some(t) => t,
+            None => return Ok(None),
+        };
+        let keyword = tokens.next().unwrap_or_default();
+
+        // Frequent commands are parsed directly from tokens
+        let command = match keyword {
+            "v" => parse_vertex(tokens),
+            "vt" => parse_vertex_uv(tokens),
+            "vn" => take_vec3(tokens).map(ObjCommand::VertexNormal),
+            "f" => match self.parse_face(tokens) {
+                Ok(()) => return Ok(Some(Decoded::Face)),
+                Err(e) => Err(e),
+            },
+            "l" => self.parse_element(tokens, 2).map(ObjCommand::Line),
+            "p" => self.parse_element(tokens, 1).map(ObjCommand::Points),
+            _ => {
+                let data: Vec<_> = tokens.collect();
+                parse_obj_line(keyword, &data, arguments(line, keyword))
+            }
+        };
+        let command = command.map_err(|e| e.at(self.location(line)))?;
+        match command {
+            ObjCommand::Vertex(..) => self.vertices += 1,
+            ObjCommand::VertexUv(..) => self.uvs += 1,
+            ObjCommand::VertexNormal(_) => self.normals += 1,
+            _ => (),
+        }
+
+        Ok(Some(Decoded::Command(command)))
+    }
+
+    /// The face last decoded.
+    pub(crate) fn face(&self) -> &[FaceIndexPair] {
+        &self.face_buffer
+    }
+
+    /// Converts the decoded line into an owned command.
+    pub(crate) fn command(&self, decoded: Decoded) -> ObjCommand {
+        match decoded {
+            Decoded::Face => ObjCommand::Face(self.face().into()),
+            Decoded::Command(command) => command,
+        }
+    }
+
+    /// Advances the state over the lines without decoding them.
+    /// Only keywords are checked to count defined elements.
+    pub(crate) fn skip(&mut self, source: &str) {
+        for line in logical_lines(source) {
+            let (line, count) = join_continued_lines(line);
+            self.line_number = self.consumed_lines + 1;
+            self.consumed_lines += count;
+            match tokenize(&line).and_then(|mut t| t.next()) {
+                Some("v") => self.vertices += 1,
+                Some("vt") => self.uvs += 1,
+                Some("vn") => self.normals += 1,
+                _ => (),
+            }
+        }
+    }
+
+    /// Advances the state as if the lines skipped by `other` follow.
+    pub(crate) fn advance(&mut self, other: &ObjDecoder) {
+        self.consumed_lines += other.consumed_lines;
+        self.vertices += other.vertices;
+        self.uvs += other.uvs;
+        self.normals += other.normals;
+    }
+
+    /// Makes the location of the current line.
+    pub(crate) fn location(&self, line: &str) -> Location {
+        let (line, _) = join_continued_lines(line);
+        Location::new(SourceFile::Obj, self.line_number, &line)
+    }
+
+    /// Makes an error for failure on reading the next line.
+    pub(crate) fn read_error(&self, error: IoError) -> Error {
+        Error::from(error).at(Location::new(SourceFile::Obj, self.consumed_lines + 1, ""))
+    }
+
+    /// Parses `l` or `p` arguments like `f`, and checks the number of vertices.
+    fn parse_element<'a>(
+        &mut self,
+        vertices: impl Iterator<Item = &'a str>,
+        minimum: usize,
+    ) -> Result<Box<[FaceIndexPair]>> {
+        self.parse_face(vertices)?;
+        if self.face_buffer.len() < minimum {
+            return Err(Error::NotEnoughData {
+                found: self.face_buffer.len(),
+                expected: minimum,
+            });
+        }
+        Ok(self.face().into())
+    }
+
+    /// Parses `f` arguments into resolved indices and checks them.
+    /// The result is stored in `face_buffer`.
+    fn parse_face<'a>(&mut self, vertices: impl Iterator<Item = &'a str>) -> Result<()> {
+        self.face_buffer.clear();
+        for vertex in vertices {
+            let parse_error = || Error::ParseError(vertex.to_string());
+            let mut indices = vertex.split('/');
+            let resolved_v = match indices.next() {
+                Some(i) if !i.is_empty() => {
+                    resolve_index(parse_index(i).ok_or_else(parse_error)?, self.vertices)?
+                }
+                _ => return Err(parse_error()),
+            };
+            let resolved_t = match indices.next() {
+                Some(i) if !i.is_empty() => Some(resolve_index(
+                    parse_index(i).ok_or_else(parse_error)?,
+                    self.uvs,
+                )?),
+                _ => None,
+            };
+            let resolved_n = match indices.next() {
+                Some(i) if !i.is_empty() => Some(resolve_index(
+                    parse_index(i).ok_or_else(parse_error)?,
+                    self.normals,
+                )?),
+                _ => None,
+            };
+            self.face_buffer
+                .push(FaceIndexPair(resolved_v, resolved_t, resolved_n));
+        }
+
+        let is_valid = self.face_buffer.iter().all(|FaceIndexPair(v, t, n)| {
+            *v < self.vertices
+                && t.iter().all(|&i| i < self.uvs)
+                && n.iter().all(|&i| i < self.normals)
+        });
+        if !is_valid {
+            return Err(Error::InvalidFaceVertex(self.face().into()));
+        }
+        Ok(())
+    }
+}
+
+/// Decodes lines of MTL file into commands.
+#[derive(Debug)]
+pub(crate) struct MtlDecoder {
+    path: Box<Path>,
+    line_number: usize,
+    consumed_lines: usize,
+}
+
+impl MtlDecoder {
+    pub(crate) fn new(path: &Path) -> MtlDecoder {
+        MtlDecoder {
+            path: path.into(),
+            line_number: 0,
+            consumed_lines: 0,
+        }
+    }
+
+    /// Decodes the next line.
+    /// Returns `None` for empty lines and comments.
+    pub(crate) fn decode(&mut self, line: &str) -> Result<Option<MtlCommand>> {
+        let (line, count) = join_continued_lines(line);
+        self.line_number = self.consumed_lines + 1;
+        self.consumed_lines += count;
+        let (keyword, data) = match split_line(&line) {
+            Some(s) => s,
+            None => return Ok(None),
+        };
+
+        let command = parse_mtl_line(keyword, &data, arguments(&line, keyword))
+            .map_err(|e| e.at(self.location(&line)))?;
+        Ok(Some(command))
+    }
+
+    /// Makes the location of the current line.
+    pub(crate) fn location(&self, line: &str) -> Location {
+        let (line, _) = join_continued_lines(line);
+        Location::new(SourceFile::Mtl(self.path.clone()), self.line_number, &line)
+    }
+
+    /// Makes an error for failure on reading the next line.
+    pub(crate) fn read_error(&self, error: IoError) -> Error {
+        let line_number = self.consumed_lines + 1;
+        let location = Location::new(SourceFile::Mtl(self.path.clone()), line_number, "");
+        Error::from(error).at(location)
+    }
+}
+
+/// Reads a line, and following lines while the line ends with `\\`.
+/// Returns the number of bytes read.
+pub(crate) fn read_logical_line(reader: &mut impl BufRead, buffer: &mut String) -> IoResult<usize> {
+    let mut total = 0;
+    loop {
+        let start = buffer.len();
+        let size = reader.read_line(buffer)?;
+        total += size;
+        if size == 0 || !is_continued(&buffer[start..]) {
+            return Ok(total);
+        }
+    }
+}
+
+/// Iterates lines in the source, keeping lines continued with `\\` together.
+pub(crate) fn logical_lines(source: &str) -> impl Iterator<Item = &str> {
+    let mut rest = source;
+    from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let mut end = 0;
+        for line in rest.split_inclusive('\n') {
+            end += line.len();
+            if !is_continued(line) {
+                break;
+            }
+        }
+        let (line, next) = rest.split_at(end);
+        rest = next;
+        Some(line)
+    })
+}
+
+/// Checks whether the physical line ends with `\\`.
+/// Comment lines are not continued.
+pub(crate) fn is_continued(line: &str) -> bool {
+    let line = trim_ascii(line);
+    !line.starts_with('#') && line.ends_with('\\')
+}
+
+/// Joins lines continued with `\\` into one line.
+/// Returns the joined line and the number of original lines.
+fn join_continued_lines(line: &str) -> (Cow<'_, str>, usize) {
+    let mut lines = line.split_inclusive('\n');
+    let first = lines.next().unwrap_or_default();
+    if !is_continued(first) {
+        return (Cow::Borrowed(line), 1);
+    }
+
+    let mut joined = String::new();
+    let mut count = 0;
+    for line in line.split_inclusive('\n') {
+        count += 1;
+        if is_continued(line) {
+            joined += line
+                .trim_end_matches(|c: char| c.is_ascii_whitespace())
+                .strip_suffix('\\')
+                .unwrap_or(line);
+            joined.push(' ');
+        } else {
+            joined += line;
+        }
+    }
+    (Cow::Owned(joined), count)
+}
+
+/// Extracts the text after the keyword, without surrounding whitespaces.
+fn arguments<'a>(line: &'a str, keyword: &str) -> &'a str {
+    let text = trim_ascii(line);
+    trim_ascii(text.strip_prefix(keyword).unwrap_or(text))
+}
+
+/// Removes leading and trailing ASCII whitespaces, which separate tokens.
+fn trim_ascii(text: &str) -> &str {
+    text.trim_matches(|c: char| c.is_ascii_whitespace())
+}
+
+/// Splits a line into whitespace-separated tokens.
+/// Returns `None` for empty lines and comments.
+fn tokenize(line: &str) -> Option<Tokens<'_>> {
+    let tokens = Tokens { line, position: 0 };
+    match tokens.clone().next() {
+        Some(first) if !first.starts_with('#') => Some(tokens),
+        _ => None,
+    }
+}
+
+/// The iterator of tokens separated with ASCII whitespaces.
+/// Bytes are scanned directly, without decoding characters.
+#[derive(Debug, Clone)]
+struct Tokens<'a> {
+    line: &'a str,
+    position: usize,
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let bytes = self.line.as_bytes();
+        let start = match bytes[self.position..]
+            .iter()
+            .position(|b| !b.is_ascii_whitespace())
+        {
+            Some(p) => self.position + p,
+            None => {
+                self.position = bytes.len();
+                return None;
+            }
+        };
+        let end = bytes[start..]
+            .iter()
+            .position(|b| b.is_ascii_whitespace())
+            .map_or(bytes.len(), |p| start + p);
+        self.position = end;
+
+        // Splitting at ASCII bytes keeps UTF-8 sequences valid
+        Some(&self.line[start..end])
+    }
+}
+
+/// Splits a line into keyword and arguments.
+/// Returns `None` for empty lines and comments.
+fn split_line(line: &str) -> Option<(&str, Vec<&str>)> {
+    let mut tokens = tokenize(line)?;
+    let keyword = tokens
+        .next()
+        .expect("Each line should have at least one element");
+    Some((keyword, tokens.collect()))
+}
+
+/// Parses `v` arguments.
+fn parse_vertex<'a>(tokens: impl IntoIterator<Item = &'a str>) -> Result<ObjCommand> {
+    let (values, count) = take_floats::<7>(tokens)?;
+    let position = Vec3::new(values[0], values[1], values[2]);
+    let command = match count {
+        0..=2 => {
+            return Err(Error::NotEnoughData {
+                found: count,
+                expected: 3,
+            })
+        }
+        3 => ObjCommand::Vertex(position, None, None),
+        4 => ObjCommand::Vertex(position, Some(values[3]), None),
+        5 => {
+            return Err(Error::NotEnoughData {
+                found: count,
+                expected: 6,
+            })
+        }
+        6 => {
+            let color = Rgb::new(values[3], values[4], values[5]);
+            ObjCommand::Vertex(position, None, Some(color))
+        }
+        _ => {
+            let color = Rgb::new(values[4], values[5], values[6]);
+            ObjCommand::Vertex(position, Some(values[3]), Some(color))
+        }
+    };
+    Ok(command)
+}
+
+/// Parses `vt` arguments.
+fn parse_vertex_uv<'a>(tokens: impl IntoIterator<Item = &'a str>) -> Result<ObjCommand> {
+    let (values, count) = take_floats::<3>(tokens)?;
+    if count == 0 {
+        return Err(Error::NotEnoughData {
+            found: 0,
+            expected: 1,
+        });
+    }
+    let w = if count == 3 { Some(values[2]) } else { None };
+    Ok(ObjCommand::VertexUv(Vec2::new(values[0], values[1]), w))
+}
+
+/// Parses a line of OBJ file.
+/// `arguments` is the text after the keyword, used for names which may contain spaces.
+fn parse_obj_line(keyword: &str, data: &[&str], arguments: &str) -> Result<ObjCommand> {
+    let value = match keyword {
+        "mtllib" => {
+            let mut filenames = vec![];
+            for filename in data {
+                let value = filename.replace("\\\\", "\\");
+                let path = PathBuf::from_str(&value).map_err(|_| Error::PathNotFound(value))?;
+                filenames.push(path.into_boxed_path());
+            }
+            ObjCommand::MaterialLibrary(filenames.into_boxed_slice())
+        }
+        "usemtl" => {
+            if arguments.is_empty() {
+                return Err(Error::NotEnoughData {
+                    expected: 1,
+                    found: 0,
+                });
+            }
+            ObjCommand::UseMaterial(arguments.into())
+        }
+        "o" => {
+            let name = Some(arguments).filter(|n| !n.is_empty());
+            ObjCommand::Object(name.map(Into::into))
+        }
+        "g" => {
+            let names: Vec<Box<str>> = data.iter().map(|&name| name.into()).collect();
+            ObjCommand::Group(names.into_boxed_slice())
+        }
+        "vp" => {
+            let (values, count) = take_floats::<3>(data)?;
+            if count == 0 {
+                return Err(Error::NotEnoughData {
+                    found: 0,
+                    expected: 1,
+                });
+            }
+            let w = if count < 3 { 1.0 } else { values[2] };
+            ObjCommand::ParameterVertex(Vec3::new(values[0], values[1], w))
+        }
+        "s" => {
+            let group = match data.first() {
+                Some(&"off") => 0,
+                _ => take_single(data)?,
+            };
+            ObjCommand::SmoothingGroup(Some(group).filter(|&g| g != 0))
+        }
+        _ => {
+            let owned_data: Vec<_> = data
+                .iter()
+                .map(|s| s.to_string().into_boxed_str())
+                .collect();
+            ObjCommand::Unknown(keyword.into(), owned_data.into_boxed_slice())
+        }
+    };
+
+    Ok(value)
+}
+
+/// Keywords for float properties without `N` prefix.
+const FLOAT_KEYWORDS: &[&str] = &[
+    "Tr",
+    "Pr",
+    "Pm",
+    "Ps",
+    "Pc",
+    "Pcr",
+    "aniso",
+    "anisor",
+    "sharpness",
+];
+
+/// Keywords for texture map without `map_` prefix.
+const TEXTURE_KEYWORDS: &[&str] = &["bump", "disp", "decal", "refl", "norm"];
+
+/// Parses a line of MTL file.
+/// `arguments` is the text after the keyword, used for names which may contain spaces.
+fn parse_mtl_line(keyword: &str, data: &[&str], arguments: &str) -> Result<MtlCommand> {
+    let value = match keyword {
+        "newmtl" => MtlCommand::NewMaterial(arguments.into()),
+        "illum" => {
+            let value = take_single(data)?;
+            MtlCommand::Integer(keyword.into(), value)
+        }
+        "d" => match data.split_first() {
+            Some((&"-halo", rest)) => MtlCommand::Dissolve(take_single(rest)?, true),
+            _ => MtlCommand::Dissolve(take_single(data)?, false),
+        },
+        k if k.starts_with('K') || k == "Tf" => match data.split_first() {
+            Some((&"spectral", rest)) => {
+                let (path, factor) = rest.split_first().ok_or(Error::NotEnoughData {
+                    found: 0,
+                    expected: 1,
+                })?;
+                let path =
+                    PathBuf::from_str(path).map_err(|_| Error::PathNotFound(path.to_string()))?;
+                let factor = factor.first().map(|f| parse_value(f)).transpose()?;
+                MtlCommand::Spectral(
+                    keyword.into(),
+                    path.into_boxed_path(),
+                    factor.unwrap_or(1.0),
+                )
+            }
+            Some((&"xyz", rest)) => MtlCommand::Xyz(keyword.into(), take_color(rest)?),
+            _ => MtlCommand::Vector(keyword.into(), take_color(data)?),
+        },
+        k if k.starts_with('N') || FLOAT_KEYWORDS.contains(&k) => {
+            let value = take_single(data)?;
+            MtlCommand::Float(keyword.into(), value)
+        }
+        k if k.starts_with("map_") || TEXTURE_KEYWORDS.contains(&k) => {
+            let (value, unknown_options) = parse_texture_map(data, arguments)?;
+            MtlCommand::Texture(keyword.into(), value, unknown_options)
+        }
+        _ => {
+            let owned_data: Vec<_> = data
+                .iter()
+                .map(|s| s.to_string().into_boxed_str())
+                .collect();
+            MtlCommand::Unknown(keyword.into(), owned_data.into_boxed_slice())
+        }
+    };
+
+    Ok(value)
+}
+
+/// Parses a texture map command with its options.
+/// Unknown options are skipped with an argument if it doesn't look like an option
+/// and is followed by the filename, and returned.
+/// `arguments` is the text of `data`, from which the filename is taken as it is.
+fn parse_texture_map(data: &[&str], arguments: &str) -> Result<(TextureMap, Box<[Box<str>]>)> {
+    let mut texture_map = TextureMap::new(PathBuf::new());
+    let mut unknown_options = vec![];
+    let mut rest = data;
+    while let Some((option, args)) = rest.split_first() {
+        if !option.starts_with('-') {
+            break;
+        }
+        rest = args;
+
+        match *option {
+            "-o" => texture_map.origin = take_option_vec3(&mut rest, texture_map.origin)?,
+            "-s" => texture_map.scale = take_option_vec3(&mut rest, texture_map.scale)?,
+            "-t" => texture_map.turbulence = take_option_vec3(&mut rest, texture_map.turbulence)?,
+            "-bm" => texture_map.bump_multiplier = Some(take_option(&mut rest)?),
+            "-clamp" => texture_map.clamp = take_option_switch(&mut rest)?,
+            "-blendu" => texture_map.blend_u = take_option_switch(&mut rest)?,
+            "-blendv" => texture_map.blend_v = take_option_switch(&mut rest)?,
+            "-cc" => texture_map.color_correction = take_option_switch(&mut rest)?,
+            "-mm" => {
+                let base = take_option(&mut rest)?;
+                let gain = take_option(&mut rest)?;
+                texture_map.value_range = (base, gain);
+            }
+            "-imfchan" => {
+                let channel = match take_option::<String>(&mut rest)?.as_str() {
+                    "r" => TextureChannel::Red,
+                    "g" => TextureChannel::Green,
+                    "b" => TextureChannel::Blue,
+                    "m" => TextureChannel::Matte,
+                    "l" => TextureChannel::Luminance,
+                    "z" => TextureChannel::Depth,
+                    other => return Err(Error::ParseError(other.to_string())),
+                };
+                texture_map.channel = Some(channel);
+            }
+            "-texres" => texture_map.resolution = Some(take_option(&mut rest)?),
+            "-type" => texture_map.map_type = Some(take_option::<String>(&mut rest)?.into()),
+            _ => {
+                if let [argument, _, ..] = rest {
+                    if !argument.starts_with('-') {
+                        rest = &rest[1..];
+                    }
+                }
+                unknown_options.push((*option).into());
+            }
+        }
+    }
+
+    // Filename may contain spaces
+    if rest.is_empty() {
+        return Err(Error::NotEnoughData {
+            found: 0,
+            expected: 1,
+        });
+    }
+    let mut options = Tokens {
+        line: arguments,
+        position: 0,
+    };
+    options
+        .by_ref()
+        .take(data.len() - rest.len())
+        .for_each(drop);
+    let value = trim_ascii(&arguments[options.position..]).replace("\\\\", "\\");
+    let path = PathBuf::from_str(&value).map_err(|_| Error::PathNotFound(value))?;
+    texture_map.path = path.into_boxed_path();
+
+    Ok((texture_map, unknown_options.into_boxed_slice()))
+}
+
+/// Consumes an argument of texture map option.
+fn take_option<T: FromStr>(rest: &mut &[&str]) -> Result<T> {
+    let (first, remaining) = rest.split_first().ok_or(Error::NotEnoughData {
+        found: 0,
+        expected: 1,
+    })?;
+    *rest = remaining;
+    parse_value(first)
+}
+
+/// Consumes an `on`/`off` argument of texture map option.
+fn take_option_switch(rest: &mut &[&str]) -> Result<bool> {
+    match take_option::<String>(rest)?.as_str() {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        other => Err(Error::ParseError(other.to_string())),
+    }
+}
+
+/// Consumes 1 to 3 arguments of texture map option.
+/// Omitted components are taken from `default`.
+fn take_option_vec3(rest: &mut &[&str], default: Vec3<f32>) -> Result<Vec3<f32>> {
+    let mut value = default;
+    value.x = take_option(rest)?;
+    for component in [&mut value.y, &mut value.z] {
+        match rest.first().map(|s| s.parse()) {
+            Some(Ok(v)) => {
+                *component = v;
+                *rest = &rest[1..];
+            }
+            _ => break,
+        }
+    }
+    Ok(value)
+}
+
+/// Parses a face index, which may be negative.
+/// Plain decimal digits are handled without `str::parse`.
+fn parse_index(token: &str) -> Option<isize> {
+    let (negative, digits) = match token.as_bytes() {
+        [b'-', rest @ ..] => (true, rest),
+        [b'+', rest @ ..] => (false, rest),
+        bytes => (false, bytes),
+    };
+    if digits.is_empty() {
+        return None;
+    }
+
+    let mut value: isize = 0;
+    for &b in digits {
+        if !b.is_ascii_digit() {
+            return None;
+        }
+        value = value.checked_mul(10)?.checked_add((b - b'0') as isize)?;
+    }
+    Some(if negative { -value } else { value })
+}
+
+/// Resolves an 1-based index (or negative relative index) into 0-based index.
+/// `defined` is the number of elements defined so far.
+fn resolve_index(raw: isize, defined: usize) -> Result<usize> {
+    match raw {
+        0 => Err(Error::InvalidIndex),
+        i if i > 0 => Ok(i as usize - 1),
+        i => defined
+            .checked_sub(i.unsigned_abs())
+            .ok_or(Error::InvalidIndex),
+    }
+}
+
+/// Parses a token into `f32`.
+/// Short decimal numbers are handled without `str::parse`, with the same result.
+fn parse_float(token: &str) -> Result<f32> {
+    match parse_short_decimal(token.as_bytes()) {
+        Some(value) => Ok(value),
+        None => parse_value(token),
+    }
+}
+
+/// Powers of 10 which are exactly representable in `f32`.
+const EXACT_POWERS_OF_10: [f32; 11] = [1e0, 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10];
+
+/// Parses a decimal number whose significand is at most 2^24 and
+/// whose exponent is at most 10 in magnitude.
+/// Both are exactly representable in `f32`, so single multiplication or
+/// division gives the correctly rounded value. Returns `None` for other numbers.
+fn parse_short_decimal(bytes: &[u8]) -> Option<f32> {
+    let (negative, bytes) = match bytes {
+        [b'-', rest @ ..] => (true, rest),
+        [b'+', rest @ ..] => (false, rest),
+        bytes => (false, bytes),
+    };
+
+    let mut significand: u64 = 0;
+    let mut digits = 0;
+    let mut exponent: i32 = 0;
+    let mut rest = bytes;
+    while let [b @ b'0'..=b'9', next @ ..] = rest {
+        significand = significand * 10 + (b - b'0') as u64;
+        digits += 1;
+        rest = next;
+        if significand > 1 << 24 {
+            return None;
+        }
+    }
+    if let [b'.', next @ ..] = rest {
+        rest = next;
+        while let [b @ b'0'..=b'9', next @ ..] = rest {
+            significand = significand * 10 + (b - b'0') as u64;
+            digits += 1;
+            exponent -= 1;
+            rest = next;
+            if significand > 1 << 24 {
+                return None;
+            }
+        }
+    }
+    if digits == 0 {
+        return None;
+    }
+    if let [b'e' | b'E', next @ ..] = rest {
+        let (exponent_negative, next) = match next {
+            [b'-', next @ ..] => (true, next),
+            [b'+', next @ ..] => (false, next),
+            next => (false, next),
+        };
+        let explicit: i32 = match next {
+            [d @ b'0'..=b'9'] => (d - b'0') as i32,
+            [d1 @ b'0'..=b'9', d2 @ b'0'..=b'9'] => ((d1 - b'0') * 10 + (d2 - b'0')) as i32,
+            _ => return None,
+        };
+        exponent += if exponent_negative {
+            -explicit
+        } else {
+            explicit
+        };
+        rest = &[];
+    }
+    if !rest.is_empty() {
+        return None;
+    }
+
+    let power = *EXACT_POWERS_OF_10.get(exponent.unsigned_abs() as usize)?;
+    let magnitude = if exponent < 0 {
+        significand as f32 / power
+    } else {
+        significand as f32 * power
+    };
+    Some(if negative { -magnitude } else { magnitude })
+}
+
+/// Parses a token into value.
+fn parse_value<T: FromStr>(token: &str) -> Result<T> {
+    token
+        .parse()
+        .map_err(|_| Error::ParseError(token.to_string()))
+}
+
+/// Consumes the iterator and parses the first element.
+pub(crate) fn take_single<T: FromStr>(it: impl IntoIterator<Item = impl AsRef<str>>) -> Result<T> {
+    let mut it = it.into_iter();
+    let first = it.next().ok_or(Error::NotEnoughData {
+        found: 0,
+        expected: 1,
+    })?;
+
+    let value = parse_value(first.as_ref())?;
+    Ok(value)
+}
+
+/// Parses at most `N` values from the iterator.
+/// Returns the values (filled with 0) and the number of parsed values.
+fn take_floats<const N: usize>(
+    it: impl IntoIterator<Item = impl AsRef<str>>,
+) -> Result<([f32; N], usize)> {
+    let mut values = [0.0; N];
+    let mut count = 0;
+    for token in it.into_iter().take(N) {
+        values[count] = parse_float(token.as_ref())?;
+        count += 1;
+    }
+    Ok((values, count))
+}
+
+/// Consumes the slice and parses into color.
+/// If only one component is given, it is used for all components.
+fn take_color(data: &[&str]) -> Result<Vec3<f32>> {
+    match data {
+        [single] => Ok(Vec3::broadcast(parse_value(single)?)),
+        _ => take_vec3(data),
+    }
+}
+
+/// Consumes the iterator and parses into `Vec3`.
+pub(crate) fn take_vec3(it: impl IntoIterator<Item = impl AsRef<str>>) -> Result<Vec3<f32>> {
+    let mut it = it.into_iter();
+    let first = it
+        .next()
+        .ok_or(Error::NotEnoughData {
+            found: 0,
+            expected: 3,
+        })
+        .and_then(|s| parse_float(s.as_ref()))?;
+    let second = it
+        .next()
+        .ok_or(Error::NotEnoughData {
+            found: 1,
+            expected: 3,
+        })
+        .and_then(|s| parse_float(s.as_ref()))?;
+    let third = it
+        .next()
+        .ok_or(Error::NotEnoughData {
+            found: 2,
+            expected: 3,
+        })
+        .and_then(|s| parse_float(s.as_ref()))?;
+
+    Ok(Vec3::new(first, second, third))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_are_split_at_ascii_whitespaces() {
+        let tokens: Vec<_> = tokenize("  v\t1.0  2.0\r\n").unwrap().collect();
+        assert_eq!(tokens, ["v", "1.0", "2.0"]);
+        let tokens: Vec<_> = tokenize("usemtl 金属 a").unwrap().collect();
+        assert_eq!(tokens, ["usemtl", "金属", "a"]);
+        assert!(tokenize(" \t\n").is_none());
+        assert!(tokenize("  # comment").is_none());
+    }
+
+    #[test]
+    fn short_decimals_parse_as_str_parse() {
+        let tokens = [
+            "0",
+            "-0",
+            "1",
+            "+1",
+            "0.1",
+            "-0.5",
+            "1.000000",
+            "0.333333",
+            "123.456",
+            "16777216",
+            "0.0000001",
+            "1e5",
+            "1.5E-3",
+            "-2.5e+10",
+            ".5",
+            "5.",
+        ];
+        for token in tokens {
+            let expected: f32 = token.parse().unwrap();
+            let parsed = parse_short_decimal(token.as_bytes()).unwrap();
+            assert_eq!(parsed.to_bits(), expected.to_bits(), "{}", token);
+        }
+    }
+
+    #[test]
+    fn other_floats_fall_back_to_str_parse() {
+        let tokens = ["16777217", "123.456789", "1e20", "1e-11", "inf", "NaN"];
+        for token in tokens {
+            assert!(parse_short_decimal(token.as_bytes()).is_none(), "{}", token);
+            let expected: f32 = token.parse().unwrap();
+            let parsed = parse_float(token).unwrap();
+            assert!(parsed == expected || parsed.is_nan() && expected.is_nan());
+        }
+        for token in ["", "-", ".", "e5", "1e", "1.0.0", "1x"] {
+            assert!(parse_short_decimal(token.as_bytes()).is_none(), "{}", token);
+        }
+        assert!(parse_float("1x").is_err());
+    }
+
+    /// Reads all logical lines with `read_logical_line`.
+    fn read_logical_lines(source: &str) -> Vec<String> {
+        let mut reader = source.as_bytes();
+        let mut lines = vec![];
+        loop {
+            let mut buffer = String::new();
+            if read_logical_line(&mut reader, &mut buffer).unwrap() == 0 {
+                return lines;
+            }
+            lines.push(buffer);
+        }
+    }
+
+    #[test]
+    fn continuation_ends_at_line_without_backslash() {
+        let source = "v 0 0 \\\n 0\nv 0 0 0 \\\n\nv 1 0 0\n";
+        let expected = ["v 0 0 \\\n 0\n", "v 0 0 0 \\\n\n", "v 1 0 0\n"];
+        assert_eq!(logical_lines(source).collect::<Vec<_>>(), expected);
+        assert_eq!(read_logical_lines(source), expected);
+        assert_eq!(
+            join_continued_lines(expected[1]),
+            (Cow::Owned("v 0 0 0  \n".into()), 2)
+        );
+    }
+
+    #[test]
+    fn comments_are_not_continued() {
+        let source = "# comment \\\nv 1 0 0\n  #\\\r\nv 2 0 0";
+        let expected = ["# comment \\\n", "v 1 0 0\n", "  #\\\r\n", "v 2 0 0"];
+        assert_eq!(logical_lines(source).collect::<Vec<_>>(), expected);
+        assert_eq!(read_logical_lines(source), expected);
+    }
+
+    #[test]
+    fn texture_options_are_parsed() {
+        let data = [
+            "-o",
+            "0.5",
+            "-s",
+            "2",
+            "3",
+            "-bm",
+            "0.2",
+            "-clamp",
+            "on",
+            "-mm",
+            "0.1",
+            "2",
+            "-imfchan",
+            "z",
+            "-type",
+            "sphere",
+            "my",
+            "texture.png",
+        ];
+        let (texture_map, unknown_options) = parse_texture_map(&data, &data.join(" ")).unwrap();
+        assert_eq!(texture_map.path(), Path::new("my texture.png"));
+        assert_eq!(texture_map.origin(), Vec3::new(0.5, 0.0, 0.0));
+        assert_eq!(texture_map.scale(), Vec3::new(2.0, 3.0, 1.0));
+        assert_eq!(texture_map.bump_multiplier(), Some(0.2));
+        assert!(texture_map.clamp());
+        assert_eq!(texture_map.value_range(), (0.1, 2.0));
+        assert_eq!(texture_map.channel(), Some(TextureChannel::Depth));
+        assert_eq!(texture_map.map_type(), Some("sphere"));
+        assert!(unknown_options.is_empty());
+    }
+
+    #[test]
+    fn unknown_texture_options_are_skipped() {
+        let data = ["-colorspace", "sRGB", "-blendu", "off", "-x", "-y", "a.png"];
+        let (texture_map, unknown_options) = parse_texture_map(&data, &data.join(" ")).unwrap();
+        assert_eq!(texture_map.path(), Path::new("a.png"));
+        assert!(!texture_map.blend_u());
+        assert_eq!(
+            &*unknown_options,
+            ["-colorspace".into(), "-x".into(), "-y".into()]
+        );
+
+        // The last argument is always the filename
+        let (texture_map, _) =
+            parse_texture_map(&["-colorspace", "a.png"], "-colorspace a.png").unwrap();
+        assert_eq!(texture_map.path(), Path::new("a.png"));
+        let data = ["-clamp", "maybe", "a.png"];
+        assert!(parse_texture_map(&data, &data.join(" ")).is_err());
+    }
+
+    #[test]
+    fn texture_filename_is_taken_as_it_is() {
+        let mut decoder = MtlDecoder::new(Path::new("a.mtl"));
+        let command = decoder
+            .decode("map_Kd  -s 2\t my  \t texture.png \r\n")
+            .unwrap();
+        match command {
+            Some(MtlCommand::Texture(_, texture_map, _)) => {
+                assert_eq!(texture_map.path(), Path::new("my  \t texture.png"));
+                assert_eq!(texture_map.scale(), Vec3::new(2.0, 1.0, 1.0));
+            }
+            other => panic!("{:?}", other),
+        }
+        let command = decoder.decode("bump a\\\\b  c.png").unwrap();
+        match command {
+            Some(MtlCommand::Texture(_, texture_map, _)) => {
+                assert_eq!(texture_map.path(), Path::new("a\\b  c.png"));
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn faces_are_kept_in_decoder() {
+        let mut decoder = ObjDecoder::default();
+        for line in ["v 0 0 0", "v 1 0 0", "v 0 1 0"] {
+            decoder.decode_line(line).unwrap();
+        }
+        assert!(matches!(
+            decoder.decode_line("f 1 2 -1").unwrap(),
+            Some(Decoded::Face)
+        ));
+        let expected = [
+            FaceIndexPair(0, None, None),
+            FaceIndexPair(1, None, None),
+            FaceIndexPair(2, None, None),
+        ];
+        assert_eq!(decoder.face(), expected);
+    }
+
+    #[test]
+    fn obj_commands_are_streamed() {
+        let source = "mtllib a.mtl b.mtl\no cube\ng side top\nv 0 0 0\nvt 0 1\n\
+                      usemtl red\nf 1/1 1/1 1/1\nvendor_tag 1 x\n";
+        let mut commands = ObjCommands::new(source.as_bytes());
+        let mut collected = vec![];
+        for command in commands.by_ref() {
+            collected.push(command.unwrap());
+        }
+        let names = |names: &[&str]| names.iter().map(|&n| n.into()).collect();
+        let paths: Box<[Box<Path>]> = ["a.mtl", "b.mtl"].map(|p| Path::new(p).into()).into();
+        let expected = [
+            ObjCommand::MaterialLibrary(paths),
+            ObjCommand::Object(Some("cube".into())),
+            ObjCommand::Group(names(&["side", "top"])),
+            ObjCommand::Vertex(Vec3::zero(), None, None),
+            ObjCommand::VertexUv(Vec2::new(0.0, 1.0), None),
+            ObjCommand::UseMaterial("red".into()),
+            ObjCommand::Face([FaceIndexPair(0, Some(0), None); 3].into()),
+            ObjCommand::Unknown("vendor_tag".into(), names(&["1", "x"])),
+        ];
+        assert_eq!(collected, expected);
+        assert_eq!(commands.location().line(), 8);
+    }
+
+    #[test]
+    fn mtl_commands_are_streamed() {
+        let source = "newmtl red metal\nKd 1 0 0\nillum 2\nvendor_tag\n";
+        let commands: Vec<_> = MtlCommands::new(source.as_bytes(), Path::new("a.mtl"))
+            .collect::<Result<_>>()
+            .unwrap();
+        let expected = [
+            MtlCommand::NewMaterial("red metal".into()),
+            MtlCommand::Vector("Kd".into(), Vec3::unit_x()),
+            MtlCommand::Integer("illum".into(), 2),
+            MtlCommand::Unknown("vendor_tag".into(), [].into()),
+        ];
+        assert_eq!(commands, expected);
+    }
+}
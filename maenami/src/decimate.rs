@@ -0,0 +1,577 @@
+use crate::{
+    obj::{FaceIndexPair, FaceList, Group, Object},
+    triangulate::triangulate_polygon,
+    WavefrontObj,
+};
+
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashSet},
+};
+
+use vek::Vec3;
+
+/// The weight of planes keeping boundaries and seams, relative to face planes.
+const CONSTRAINT_WEIGHT: f64 = 100.0;
+
+impl WavefrontObj {
+    /// Simplifies all groups into `ratio` of their triangle counts.
+    /// See `Group::decimate` for details.
+    pub fn decimate(&self, ratio: f32) -> WavefrontObj {
+        WavefrontObj {
+            objects: self.objects.iter().map(|o| o.decimate(ratio)).collect(),
+            materials: self.materials.clone(),
+            parameter_vertices: self.parameter_vertices.clone(),
+            diagnostics: self.diagnostics.clone(),
+        }
+    }
+}
+
+impl Object {
+    /// Simplifies all groups into `ratio` of their triangle counts.
+    /// See `Group::decimate` for details.
+    pub fn decimate(&self, ratio: f32) -> Object {
+        Object {
+            name: self.name.clone(),
+            groups: self
+                .groups
+                .iter()
+                .map(|g| g.decimate(target_count(g.triangle_count(), ratio)))
+                .collect(),
+        }
+    }
+}
+
+impl Group {
+    /// Simplifies faces into at most `target_triangles` triangles with quadric error metrics.
+    /// Vertices are collapsed into their neighbors, so vertex data are shared as they are.
+    /// Collapses which would move UV/normal seams, material or smoothing group borders,
+    /// or open boundaries, or flip faces, are not performed; the result may have
+    /// more triangles than the target.
+    /// Faces are triangulated (dropping ones with repeated vertices),
+    /// and lines and points are kept as they are.
+    pub fn decimate(&self, target_triangles: usize) -> Group {
+        let mut simplifier = Simplifier::new(self);
+        simplifier.run(target_triangles);
+        Group {
+            name: self.name.clone(),
+            names: self.names.clone(),
+            vertices: self.vertices.clone(),
+            texture_uvs: self.texture_uvs.clone(),
+            normals: self.normals.clone(),
+            colors: self.colors.clone(),
+            vertex_ws: self.vertex_ws.clone(),
+            texture_ws: self.texture_ws.clone(),
+            face_index_pairs: simplifier.finish(),
+            lines: self.lines.clone(),
+            points: self.points.clone(),
+        }
+    }
+
+    /// Generates `levels` levels of detail, each of which has `ratio` of triangles
+    /// of the previous one. The first level is simplified from this group.
+    pub fn lod_chain(&self, ratio: f32, levels: usize) -> Vec<Group> {
+        let mut chain: Vec<Group> = vec![];
+        for _ in 0..levels {
+            let previous = chain.last().unwrap_or(self);
+            let target = target_count(previous.triangle_count(), ratio);
+            chain.push(previous.decimate(target));
+        }
+        chain
+    }
+
+    /// The number of triangles after triangulation.
+    fn triangle_count(&self) -> usize {
+        self.face_index_pairs
+            .iter()
+            .map(|(f, _, _)| f.len().saturating_sub(2))
+            .sum()
+    }
+}
+
+fn target_count(count: usize, ratio: f32) -> usize {
+    (count as f32 * ratio.clamp(0.0, 1.0)).round() as usize
+}
+
+/// Represents a symmetric 4x4 matrix of quadric error.
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    /// Creates the quadric measuring squared distance from the plane.
+    fn plane(normal: Vec3<f64>, point: Vec3<f64>, weight: f64) -> Quadric {
+        let [a, b, c] = normal.into_array();
+        let d = -normal.dot(point);
+        let q = [
+            a * a,
+            a * b,
+            a * c,
+            a * d,
+            b * b,
+            b * c,
+            b * d,
+            c * c,
+            c * d,
+            d * d,
+        ];
+        Quadric(q.map(|v| v * weight))
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (v, o) in self.0.iter_mut().zip(other.0) {
+            *v += o;
+        }
+    }
+
+    fn error(&self, p: Vec3<f64>) -> f64 {
+        let [a2, ab, ac, ad, b2, bc, bd, c2, cd, d2] = self.0;
+        let Vec3 { x, y, z } = p;
+        a2 * x * x
+            + 2.0 * ab * x * y
+            + 2.0 * ac * x * z
+            + 2.0 * ad * x
+            + b2 * y * y
+            + 2.0 * bc * y * z
+            + 2.0 * bd * y
+            + c2 * z * z
+            + 2.0 * cd * z
+            + d2
+    }
+}
+
+/// Represents an edge collapse in priority queue.
+#[derive(Debug, Clone, Copy)]
+struct Collapse {
+    cost: f64,
+    from: usize,
+    to: usize,
+    versions: (u32, u32),
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cost
+            .total_cmp(&other.cost)
+            .then((self.from, self.to).cmp(&(other.from, other.to)))
+    }
+}
+
+/// Collapses edges of triangles in a group.
+#[derive(Debug)]
+struct Simplifier<'a> {
+    group: &'a Group,
+
+    /// Vertices referenced by triangles, in indices of the shared vertex pool.
+    /// Vertex indices of triangles and other arrays refer to this.
+    vertices: Vec<usize>,
+    triangles: Vec<[FaceIndexPair; 3]>,
+
+    /// Material and smoothing group of triangles.
+    keys: Vec<(Option<usize>, Option<u32>)>,
+    alive: Vec<bool>,
+    alive_count: usize,
+    vertex_triangles: Vec<Vec<usize>>,
+    quadrics: Vec<Quadric>,
+    versions: Vec<u32>,
+    queue: BinaryHeap<Reverse<Collapse>>,
+}
+
+impl<'a> Simplifier<'a> {
+    fn new(group: &'a Group) -> Simplifier<'a> {
+        let mut triangles = vec![];
+        let mut keys = vec![];
+        for (face, material, smoothing) in group.face_index_pairs.iter() {
+            let positions: Vec<_> = face.iter().map(|p| group.vertices[p.0]).collect();
+            for [a, b, c] in triangulate_polygon(&positions) {
+                // Triangles with repeated vertices have no area, and break adjacency
+                if face[a].0 == face[b].0 || face[b].0 == face[c].0 || face[c].0 == face[a].0 {
+                    continue;
+                }
+                triangles.push([face[a], face[b], face[c]]);
+                keys.push((material, smoothing));
+            }
+        }
+
+        // The vertex pool is shared by all groups, so only referenced vertices are kept
+        let mut vertices: Vec<_> = triangles.iter().flatten().map(|p| p.0).collect();
+        vertices.sort_unstable();
+        vertices.dedup();
+        for pair in triangles.iter_mut().flatten() {
+            pair.0 = vertices
+                .binary_search(&pair.0)
+                .expect("Vertex should be collected");
+        }
+
+        let vertex_count = vertices.len();
+        let mut vertex_triangles = vec![vec![]; vertex_count];
+        for (i, triangle) in triangles.iter().enumerate() {
+            for pair in triangle {
+                vertex_triangles[pair.0].push(i);
+            }
+        }
+
+        let mut simplifier = Simplifier {
+            group,
+            vertices,
+            alive: vec![true; triangles.len()],
+            alive_count: triangles.len(),
+            triangles,
+            keys,
+            vertex_triangles,
+            quadrics: vec![Quadric::default(); vertex_count],
+            versions: vec![0; vertex_count],
+            queue: BinaryHeap::new(),
+        };
+        simplifier.initialize_quadrics();
+        simplifier
+    }
+
+    /// Accumulates face planes, and constraint planes along boundaries and seams.
+    fn initialize_quadrics(&mut self) {
+        for (i, triangle) in self.triangles.iter().enumerate() {
+            let positions = triangle.map(|p| self.position(p.0));
+            let normal = (positions[1] - positions[0]).cross(positions[2] - positions[0]);
+            let area = normal.magnitude() / 2.0;
+            let normal = match normal.try_normalized() {
+                Some(n) => n,
+                None => continue,
+            };
+
+            let face_quadric = Quadric::plane(normal, positions[0], area);
+            for pair in triangle {
+                self.quadrics[pair.0].add(&face_quadric);
+            }
+
+            for corner in 0..3 {
+                let (a, b) = (triangle[corner].0, triangle[(corner + 1) % 3].0);
+                if !self.is_constrained_edge(i, a, b) {
+                    continue;
+                }
+                let edge = positions[(corner + 1) % 3] - positions[corner];
+                let edge_normal = match edge.cross(normal).try_normalized() {
+                    Some(n) => n,
+                    None => continue,
+                };
+                let weight = CONSTRAINT_WEIGHT * edge.magnitude_squared();
+                let constraint = Quadric::plane(edge_normal, positions[corner], weight);
+                self.quadrics[a].add(&constraint);
+                self.quadrics[b].add(&constraint);
+            }
+        }
+    }
+
+    /// Checks whether the edge of the triangle is on a boundary or a seam.
+    fn is_constrained_edge(&self, triangle: usize, a: usize, b: usize) -> bool {
+        let others: Vec<_> = self
+            .edge_triangles(a, b)
+            .filter(|t| *t != triangle)
+            .collect();
+        match others[..] {
+            [other] => {
+                self.keys[triangle] != self.keys[other]
+                    || self.corner(triangle, a) != self.corner(other, a)
+                    || self.corner(triangle, b) != self.corner(other, b)
+            }
+            _ => true,
+        }
+    }
+
+    /// Collapses edges until the triangle count reaches the target.
+    fn run(&mut self, target_triangles: usize) {
+        let mut edges = HashSet::new();
+        for triangle in self.triangles.iter() {
+            for corner in 0..3 {
+                let (a, b) = (triangle[corner].0, triangle[(corner + 1) % 3].0);
+                edges.insert((a.min(b), a.max(b)));
+            }
+        }
+        let mut edges: Vec<_> = edges.into_iter().collect();
+        edges.sort_unstable();
+        for (a, b) in edges {
+            self.push_candidate(a, b);
+        }
+
+        while self.alive_count > target_triangles {
+            let Reverse(collapse) = match self.queue.pop() {
+                Some(c) => c,
+                None => break,
+            };
+            if collapse.versions != (self.versions[collapse.from], self.versions[collapse.to]) {
+                continue;
+            }
+
+            // Neighborhood may have changed without changing versions
+            let replacements = match self.plan(collapse.from, collapse.to) {
+                Some(r) => r,
+                None => continue,
+            };
+            self.collapse(collapse.from, collapse.to, replacements);
+        }
+    }
+
+    /// Evaluates both directions of the edge and queues the cheaper valid one.
+    fn push_candidate(&mut self, a: usize, b: usize) {
+        let best = [(a, b), (b, a)]
+            .into_iter()
+            .filter(|&(from, to)| self.plan(from, to).is_some())
+            .map(|(from, to)| {
+                let mut quadric = self.quadrics[from];
+                quadric.add(&self.quadrics[to]);
+                let cost = quadric.error(self.position(to)).max(0.0);
+                Collapse {
+                    cost,
+                    from,
+                    to,
+                    versions: (self.versions[from], self.versions[to]),
+                }
+            })
+            .min();
+        if let Some(collapse) = best {
+            self.queue.push(Reverse(collapse));
+        }
+    }
+
+    /// Checks whether `from` can be collapsed into `to`.
+    /// Returns the replaced corners of triangles around `from`.
+    fn plan(&self, from: usize, to: usize) -> Option<Vec<(usize, FaceIndexPair)>> {
+        let edge_triangles: Vec<_> = self.edge_triangles(from, to).collect();
+        if edge_triangles.is_empty() || edge_triangles.len() > 2 {
+            return None;
+        }
+        // Boundaries only move along themselves
+        if edge_triangles.len() == 2 && self.is_boundary_vertex(from) {
+            return None;
+        }
+
+        // Each corner of `from` must have the same attributes in a triangle on the edge,
+        // otherwise seams or borders would move
+        let mut replacements = vec![];
+        for &t in self.vertex_triangles[from].iter() {
+            if edge_triangles.contains(&t) {
+                continue;
+            }
+            let corner = self.corner(t, from)?;
+            let source = edge_triangles.iter().find(|&&e| {
+                self.keys[e] == self.keys[t]
+                    && self.corner(e, from).map(|c| (c.1, c.2)) == Some((corner.1, corner.2))
+            })?;
+            replacements.push((t, self.corner(*source, to)?));
+        }
+
+        // Faces must not be flipped
+        for &(t, _) in replacements.iter() {
+            let before = self.triangles[t].map(|p| self.position(p.0));
+            let after =
+                self.triangles[t].map(|p| self.position(if p.0 == from { to } else { p.0 }));
+            let before = (before[1] - before[0]).cross(before[2] - before[0]);
+            let after = (after[1] - after[0]).cross(after[2] - after[0]);
+            if before != Vec3::zero() && before.dot(after) <= 0.0 {
+                return None;
+            }
+        }
+
+        // Link condition: common neighbors must be the opposite vertices of the edge triangles
+        let neighbors = |v: usize| -> HashSet<usize> {
+            self.vertex_triangles[v]
+                .iter()
+                .flat_map(|&t| self.triangles[t].map(|p| p.0))
+                .filter(|&w| w != v)
+                .collect()
+        };
+        let common = neighbors(from).intersection(&neighbors(to)).count();
+        if common != edge_triangles.len() {
+            return None;
+        }
+
+        Some(replacements)
+    }
+
+    /// Collapses `from` into `to`.
+    fn collapse(&mut self, from: usize, to: usize, replacements: Vec<(usize, FaceIndexPair)>) {
+        let removed: Vec<_> = self.edge_triangles(from, to).collect();
+        for t in removed {
+            self.alive[t] = false;
+            self.alive_count -= 1;
+            for pair in self.triangles[t] {
+                self.vertex_triangles[pair.0].retain(|&u| u != t);
+            }
+        }
+
+        for (t, replacement) in replacements {
+            for pair in self.triangles[t].iter_mut() {
+                if pair.0 == from {
+                    *pair = replacement;
+                }
+            }
+            self.vertex_triangles[to].push(t);
+        }
+        self.vertex_triangles[from].clear();
+
+        let quadric = self.quadrics[from];
+        self.quadrics[to].add(&quadric);
+        self.versions[from] += 1;
+        self.versions[to] += 1;
+
+        let mut neighbors: Vec<_> = self.vertex_triangles[to]
+            .iter()
+            .flat_map(|&t| self.triangles[t].map(|p| p.0))
+            .filter(|&w| w != to)
+            .collect();
+        neighbors.sort_unstable();
+        neighbors.dedup();
+        for neighbor in neighbors {
+            self.push_candidate(to, neighbor);
+        }
+    }
+
+    /// Returns remaining triangles as faces.
+    fn finish(self) -> FaceList {
+        let mut faces = FaceList::default();
+        for ((triangle, &(material, smoothing)), _) in self
+            .triangles
+            .iter()
+            .zip(self.keys.iter())
+            .zip(self.alive.iter())
+            .filter(|(_, alive)| **alive)
+        {
+            let triangle = triangle.map(|p| FaceIndexPair(self.vertices[p.0], p.1, p.2));
+            faces.push(&triangle, material, smoothing);
+        }
+        faces
+    }
+
+    fn position(&self, v: usize) -> Vec3<f64> {
+        self.group.vertices[self.vertices[v]].as_()
+    }
+
+    /// Returns the corner of the vertex in the triangle.
+    fn corner(&self, triangle: usize, v: usize) -> Option<FaceIndexPair> {
+        self.triangles[triangle].into_iter().find(|p| p.0 == v)
+    }
+
+    /// Iterates alive triangles which have both vertices.
+    fn edge_triangles(&self, a: usize, b: usize) -> impl Iterator<Item = usize> + '_ {
+        self.vertex_triangles[a]
+            .iter()
+            .copied()
+            .filter(move |&t| self.triangles[t].iter().any(|p| p.0 == b))
+    }
+
+    /// Checks whether the vertex has an edge used by only one triangle.
+    fn is_boundary_vertex(&self, v: usize) -> bool {
+        self.vertex_triangles[v].iter().any(|&t| {
+            self.triangles[t]
+                .iter()
+                .filter(|p| p.0 != v)
+                .any(|p| self.edge_triangles(v, p.0).count() == 1)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Parser, WavefrontObj};
+
+    use std::{collections::HashSet, fmt::Write, io::Cursor};
+
+    fn parse(source: &str) -> WavefrontObj {
+        let mut parser = Parser::new(|_, _: &()| Ok(Cursor::new(vec![])));
+        parser.parse(source.as_bytes(), ()).unwrap()
+    }
+
+    /// Flat grid of `n` x `n` vertices split into two smoothing groups at x = `n / 2`.
+    fn grid(n: usize) -> String {
+        let mut source = String::new();
+        for y in 0..n {
+            for x in 0..n {
+                writeln!(source, "v {} {} 0", x, y).unwrap();
+            }
+        }
+        for half in 0..2 {
+            writeln!(source, "s {}", half + 1).unwrap();
+            for y in 0..n - 1 {
+                for x in half * (n / 2)..(half + 1) * (n / 2) {
+                    let v = y * n + x + 1;
+                    writeln!(source, "f {} {} {} {}", v, v + 1, v + n + 1, v + n).unwrap();
+                }
+            }
+        }
+        source
+    }
+
+    #[test]
+    fn reduces_flat_grid() {
+        let obj = parse(&grid(9));
+        let group = &obj.objects()[0].groups()[0];
+        assert_eq!(group.triangle_count(), 128);
+
+        let decimated = group.decimate(16);
+        // Flat interior has no error, so the target is reached
+        assert!(decimated.triangle_count() <= 16);
+    }
+
+    #[test]
+    fn keeps_smoothing_group_borders() {
+        let obj = parse(&grid(9));
+        let group = obj.objects()[0].groups()[0].decimate(0);
+        let vertices = group.vertices();
+        for (face, _, smoothing) in group.face_index_pairs() {
+            for pair in face {
+                let x = vertices[pair.0].x;
+                match smoothing {
+                    Some(1) => assert!(x <= 4.0),
+                    Some(2) => assert!(x >= 4.0),
+                    _ => unreachable!(),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_indices_of_shared_pool() {
+        let mut source = grid(5);
+        source.insert_str(0, "g first\n");
+        source.push_str("g second\nf 1 2 7 6\nf 11 12 17 16\n");
+        let obj = parse(&source);
+        let groups = obj.objects()[0].groups();
+        let referenced: HashSet<_> = groups[1]
+            .face_index_pairs()
+            .iter()
+            .flat_map(|(f, _, _)| f.iter().map(|p| p.0))
+            .collect();
+
+        let decimated = groups[1].decimate(0);
+        assert_eq!(decimated.vertices().len(), groups[1].vertices().len());
+        for (face, _, _) in decimated.face_index_pairs() {
+            assert!(face.iter().all(|p| referenced.contains(&p.0)));
+        }
+    }
+
+    #[test]
+    fn lod_chain_reduces_triangles() {
+        let obj = parse(&grid(9));
+        let group = &obj.objects()[0].groups()[0];
+        let chain = group.lod_chain(0.5, 3);
+        assert_eq!(chain.len(), 3);
+        let mut previous = group.triangle_count();
+        for level in chain.iter() {
+            assert!(level.triangle_count() <= previous);
+            previous = level.triangle_count();
+        }
+        assert!(previous < group.triangle_count());
+    }
+}
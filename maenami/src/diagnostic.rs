@@ -0,0 +1,169 @@
+use crate::{Error, Location, Result};
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use log::warn;
+
+/// Represents the kind of problem which doesn't prevent parsing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DiagnosticKind {
+    /// Unknown keyword in OBJ/MTL file.
+    UnknownKeyword(Box<str>),
+
+    /// `usemtl` referring a material not defined in loaded MTL files.
+    UnknownMaterial(Box<str>),
+
+    /// Unknown option of texture map in MTL file, which is skipped.
+    UnknownTextureOption(Box<str>),
+}
+
+impl Display for DiagnosticKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            DiagnosticKind::UnknownKeyword(keyword) => {
+                write!(f, "Unknown keyword: \"{}\"", keyword)
+            }
+            DiagnosticKind::UnknownMaterial(name) => write!(f, "Unknown material: \"{}\"", name),
+            DiagnosticKind::UnknownTextureOption(option) => {
+                write!(f, "Unknown texture option: \"{}\"", option)
+            }
+        }
+    }
+}
+
+/// Represents a problem found in parsing, with its location.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    kind: DiagnosticKind,
+    location: Location,
+}
+
+impl Diagnostic {
+    /// The kind of the problem.
+    pub fn kind(&self) -> &DiagnosticKind {
+        &self.kind
+    }
+
+    /// The location where the problem was found.
+    pub fn location(&self) -> &Location {
+        &self.location
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}: {}", self.location, self.kind)
+    }
+}
+
+/// Collects diagnostics, or turns them into errors in strict mode.
+#[derive(Debug, Default)]
+pub(crate) struct Diagnostics {
+    strict: bool,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub(crate) fn new(strict: bool) -> Diagnostics {
+        Diagnostics {
+            strict,
+            diagnostics: vec![],
+        }
+    }
+
+    /// Reports a problem.
+    /// Returns an error in strict mode.
+    pub(crate) fn report(&mut self, kind: DiagnosticKind, location: Location) -> Result<()> {
+        if self.strict {
+            return Err(Error::Diagnostic(kind).at(location));
+        }
+
+        warn!("{}: {}", location, kind);
+        self.diagnostics.push(Diagnostic { kind, location });
+        Ok(())
+    }
+
+    /// Appends diagnostics collected elsewhere.
+    pub(crate) fn extend(&mut self, diagnostics: Vec<Diagnostic>) {
+        self.diagnostics.extend(diagnostics);
+    }
+
+    /// Finishes collecting.
+    pub(crate) fn finish(self) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parser, SourceFile, WavefrontObj};
+
+    use std::{io::Cursor, path::Path};
+
+    const SOURCE: &str = "\
+mtllib a.mtl
+v 0 0 0
+vendor_tag 1
+usemtl red
+usemtl blue
+";
+
+    fn parse(strict: bool) -> Result<WavefrontObj> {
+        let mut parser =
+            Parser::new(|_, _: &()| Ok(Cursor::new("newmtl red\nvendor_mtl\n"))).strict(strict);
+        parser.parse(SOURCE.as_bytes(), ())
+    }
+
+    #[test]
+    fn problems_are_collected() {
+        let obj = parse(false).unwrap();
+        let diagnostics: Vec<_> = obj
+            .diagnostics()
+            .iter()
+            .map(|d| {
+                (
+                    d.kind().clone(),
+                    d.location().file().clone(),
+                    d.location().line(),
+                )
+            })
+            .collect();
+        let expected = [
+            (
+                DiagnosticKind::UnknownKeyword("vendor_mtl".into()),
+                SourceFile::Mtl(Path::new("a.mtl").into()),
+                2,
+            ),
+            (
+                DiagnosticKind::UnknownKeyword("vendor_tag".into()),
+                SourceFile::Obj,
+                3,
+            ),
+            (
+                DiagnosticKind::UnknownMaterial("blue".into()),
+                SourceFile::Obj,
+                5,
+            ),
+        ];
+        assert_eq!(diagnostics, expected);
+        assert_eq!(
+            obj.diagnostics()[2].to_string(),
+            "OBJ line 5 (\"usemtl blue\"): Unknown material: \"blue\""
+        );
+    }
+
+    #[test]
+    fn problems_are_errors_in_strict_mode() {
+        let error = parse(true).unwrap_err();
+        assert_eq!(error.location().unwrap().line(), 2);
+        match error {
+            Error::Located { error, .. } => assert!(matches!(
+                *error,
+                Error::Diagnostic(DiagnosticKind::UnknownKeyword(_))
+            )),
+            other => panic!("{:?}", other),
+        }
+    }
+}
@@ -0,0 +1,674 @@
+use crate::{
+    mesh::IndexedMesh,
+    mtl::{Material, TextureMap},
+    obj::{Group, Object},
+    Error, Result, WavefrontObj,
+};
+
+use std::{
+    collections::HashMap,
+    io::{prelude::*, BufWriter, Error as IoError, ErrorKind},
+    path::Path,
+};
+
+use serde_json::{json, Value};
+use vek::Vec3;
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const UNSIGNED_INT: u32 = 5125;
+const FLOAT: u32 = 5126;
+const CLAMP_TO_EDGE: u32 = 33071;
+const MODE_POINTS: u32 = 0;
+const MODE_LINES: u32 = 1;
+
+const GLB_MAGIC: u32 = 0x4654_6C67;
+const GLB_VERSION: u32 = 2;
+const GLB_CHUNK_JSON: u32 = 0x4E4F_534A;
+const GLB_CHUNK_BIN: u32 = 0x004E_4942;
+
+impl WavefrontObj {
+    /// Writes objects and materials as glTF 2.0 JSON, and vertex data as its binary buffer.
+    /// `buffer_path` is the path of binary buffer referred from JSON, relative to JSON file.
+    /// See `write_glb` for how objects and materials are converted.
+    /// The writers will be wrapped with `BufWriter`, so you don't have to do so.
+    pub fn write_gltf(
+        &self,
+        json_writer: impl Write,
+        buffer_writer: impl Write,
+        buffer_path: &Path,
+    ) -> Result<()> {
+        let gltf = GltfBuilder::build(self)?;
+
+        let mut json_writer = BufWriter::new(json_writer);
+        serde_json::to_writer(&mut json_writer, &gltf.json(Some(buffer_path)))
+            .map_err(IoError::from)?;
+        json_writer.flush()?;
+
+        let mut buffer_writer = BufWriter::new(buffer_writer);
+        buffer_writer.write_all(&gltf.buffer)?;
+        buffer_writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes objects and materials as single binary glTF (GLB) file.
+    /// Each `Object` becomes a node, whose children are nodes with a mesh for each `Group`.
+    /// Faces are triangulated, and each material in a group becomes a primitive.
+    /// Lines and points become primitives with `LINES` and `POINTS` mode.
+    /// Materials are converted to PBR metallic-roughness; `Kd`, `d`, `Pm`, `Pr` (or `Ns`),
+    /// `Ke`, `map_Kd`, `norm` and `map_Ke` are used.
+    /// Texture paths are written as relative URIs, and images are not embedded.
+    /// The writer will be wrapped with `BufWriter`, so you don't have to do so.
+    pub fn write_glb(&self, writer: impl Write) -> Result<()> {
+        let gltf = GltfBuilder::build(self)?;
+        let mut json = serde_json::to_vec(&gltf.json(None)).map_err(IoError::from)?;
+        let mut buffer = gltf.buffer;
+        pad(&mut json, b' ');
+        pad(&mut buffer, 0);
+
+        let bin_chunk_length = match buffer.len() {
+            0 => 0,
+            l => 8 + l,
+        };
+        let total_length = u32::try_from(12 + 8 + json.len() + bin_chunk_length)
+            .map_err(|_| IoError::new(ErrorKind::InvalidData, "GLB exceeds 4 GiB"))?;
+
+        let mut writer = BufWriter::new(writer);
+        for value in [GLB_MAGIC, GLB_VERSION, total_length] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        writer.write_all(&(json.len() as u32).to_le_bytes())?;
+        writer.write_all(&GLB_CHUNK_JSON.to_le_bytes())?;
+        writer.write_all(&json)?;
+        if !buffer.is_empty() {
+            writer.write_all(&(buffer.len() as u32).to_le_bytes())?;
+            writer.write_all(&GLB_CHUNK_BIN.to_le_bytes())?;
+            writer.write_all(&buffer)?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Collects glTF entries and the binary buffer.
+#[derive(Debug, Default)]
+struct GltfBuilder {
+    buffer: Vec<u8>,
+    buffer_views: Vec<Value>,
+    accessors: Vec<Value>,
+    meshes: Vec<Value>,
+    nodes: Vec<Value>,
+    scene_nodes: Vec<usize>,
+    materials: Vec<Value>,
+    textures: Vec<(Box<Path>, bool)>,
+    images: Vec<Box<Path>>,
+}
+
+impl GltfBuilder {
+    /// Converts whole objects and materials.
+    /// Material indices are kept as they are.
+    fn build(obj: &WavefrontObj) -> Result<GltfBuilder> {
+        let mut builder = GltfBuilder::default();
+        for material in obj.materials.iter() {
+            let material = builder.material(material);
+            builder.materials.push(material);
+        }
+        for object in obj.objects.iter() {
+            let node = builder.push_object(object)?;
+            builder.scene_nodes.push(node);
+        }
+        Ok(builder)
+    }
+
+    /// Builds glTF JSON.
+    /// `buffer_path` is omitted for GLB.
+    fn json(&self, buffer_path: Option<&Path>) -> Value {
+        let buffers: Vec<_> = match self.buffer.len() {
+            0 => vec![],
+            length => vec![compact(json!({
+                "byteLength": length,
+                "uri": buffer_path.map(uri),
+            }))],
+        };
+        let textures: Vec<_> = self
+            .textures
+            .iter()
+            .map(|(path, clamp)| {
+                compact(json!({
+                    "source": self.images.iter().position(|i| i == path),
+                    "sampler": clamp.then_some(0),
+                }))
+            })
+            .collect();
+        let images: Vec<_> = self
+            .images
+            .iter()
+            .map(|path| json!({ "uri": uri(path) }))
+            .collect();
+        let mut samplers = vec![];
+        if self.textures.iter().any(|(_, clamp)| *clamp) {
+            samplers.push(json!({ "wrapS": CLAMP_TO_EDGE, "wrapT": CLAMP_TO_EDGE }));
+        }
+
+        compact(json!({
+            "asset": { "version": "2.0", "generator": "maenami" },
+            "scene": 0,
+            "scenes": [compact(json!({ "nodes": self.scene_nodes }))],
+            "nodes": self.nodes,
+            "meshes": self.meshes,
+            "materials": self.materials,
+            "textures": textures,
+            "images": images,
+            "samplers": samplers,
+            "accessors": self.accessors,
+            "bufferViews": self.buffer_views,
+            "buffers": buffers,
+        }))
+    }
+
+    /// Appends the node of object and its children.
+    /// Returns the node index.
+    fn push_object(&mut self, object: &Object) -> Result<usize> {
+        let children = object
+            .groups
+            .iter()
+            .map(|g| self.push_group(g))
+            .collect::<Result<Vec<_>>>()?;
+        let node = compact(json!({ "name": object.name, "children": children }));
+        self.nodes.push(node);
+        Ok(self.nodes.len() - 1)
+    }
+
+    /// Appends the node of group and its mesh.
+    /// Returns the node index.
+    fn push_group(&mut self, group: &Group) -> Result<usize> {
+        let mut primitives = vec![];
+        let mesh = group.to_indexed_mesh()?;
+        if !mesh.indices.is_empty() {
+            let attributes = self.push_mesh_attributes(&mesh);
+            for range in mesh.draw_ranges.iter() {
+                let range_indices = range.indices();
+                let indices =
+                    &mesh.indices[range_indices.start as usize..range_indices.end as usize];
+                let indices = self.push_indices(indices);
+                primitives.push(primitive(&attributes, indices, range.material(), None));
+            }
+        }
+        self.push_elements(group, &mut primitives)?;
+
+        let mut mesh = None;
+        if !primitives.is_empty() {
+            let json = compact(json!({ "name": group.name, "primitives": primitives }));
+            self.meshes.push(json);
+            mesh = Some(self.meshes.len() - 1);
+        }
+        let node = compact(json!({ "name": group.name, "mesh": mesh }));
+        self.nodes.push(node);
+        Ok(self.nodes.len() - 1)
+    }
+
+    /// Appends vertex attributes of triangle list.
+    /// Returns `attributes` of primitives.
+    fn push_mesh_attributes(&mut self, mesh: &IndexedMesh) -> Value {
+        let positions: Vec<_> = mesh.vertices.iter().map(|v| v.position).collect();
+        let mut attributes = json!({
+            "POSITION": self.push_vertex_data(&positions, "VEC3", true),
+        });
+        if mesh.has_normals {
+            // glTF requires unit normals
+            let normals: Vec<_> = mesh
+                .vertices
+                .iter()
+                .map(|v| {
+                    let normal = Vec3::from(v.normal);
+                    normal
+                        .try_normalized()
+                        .unwrap_or_else(Vec3::unit_z)
+                        .into_array()
+                })
+                .collect();
+            attributes["NORMAL"] = self.push_vertex_data(&normals, "VEC3", false).into();
+        }
+        if mesh.has_uvs {
+            // glTF has the origin of UV at top left
+            let uvs: Vec<_> = mesh
+                .vertices
+                .iter()
+                .map(|v| [v.uv[0], 1.0 - v.uv[1]])
+                .collect();
+            attributes["TEXCOORD_0"] = self.push_vertex_data(&uvs, "VEC2", false).into();
+        }
+        if let Some(colors) = &mesh.colors {
+            attributes["COLOR_0"] = self.push_vertex_data(colors, "VEC3", false).into();
+        }
+        attributes
+    }
+
+    /// Appends primitives of lines and points, which have positions and colors.
+    fn push_elements(&mut self, group: &Group, primitives: &mut Vec<Value>) -> Result<()> {
+        let mut vertex_indices = HashMap::new();
+        let mut positions = vec![];
+        let mut colors = vec![];
+        let mut lists: Vec<(u32, Option<usize>, Vec<u32>)> = vec![];
+
+        let lines = group.lines.iter().map(|e| (MODE_LINES, e));
+        let points = group.points.iter().map(|e| (MODE_POINTS, e));
+        for (mode, (pairs, material)) in lines.chain(points) {
+            let indices = pairs
+                .iter()
+                .map(|p| {
+                    if let Some(&index) = vertex_indices.get(&p.0) {
+                        return Ok(index);
+                    }
+                    let index =
+                        u32::try_from(positions.len()).map_err(|_| Error::TooManyVertices)?;
+                    positions.push(group.vertices[p.0].into_array());
+                    if let Some(c) = &group.colors {
+                        colors.push(c[p.0].into_array());
+                    }
+                    vertex_indices.insert(p.0, index);
+                    Ok(index)
+                })
+                .collect::<Result<Vec<u32>>>()?;
+
+            let position = match lists
+                .iter()
+                .position(|(m, l, _)| *m == mode && l == material)
+            {
+                Some(p) => p,
+                None => {
+                    lists.push((mode, *material, vec![]));
+                    lists.len() - 1
+                }
+            };
+            let list = &mut lists[position].2;
+            if mode == MODE_LINES {
+                for segment in indices.windows(2) {
+                    list.extend_from_slice(segment);
+                }
+            } else {
+                list.extend(indices);
+            }
+        }
+        if positions.is_empty() {
+            return Ok(());
+        }
+
+        let mut attributes = json!({
+            "POSITION": self.push_vertex_data(&positions, "VEC3", true),
+        });
+        if !colors.is_empty() {
+            attributes["COLOR_0"] = self.push_vertex_data(&colors, "VEC3", false).into();
+        }
+        for (mode, material, list) in lists {
+            let indices = self.push_indices(&list);
+            primitives.push(primitive(&attributes, indices, material, Some(mode)));
+        }
+        Ok(())
+    }
+
+    /// Appends float vertex data and its accessor.
+    /// Returns the accessor index.
+    fn push_vertex_data<const N: usize>(
+        &mut self,
+        data: &[[f32; N]],
+        accessor_type: &str,
+        bounds: bool,
+    ) -> usize {
+        let offset = self.align();
+        for value in data.iter().flatten() {
+            self.buffer.extend_from_slice(&value.to_le_bytes());
+        }
+        let view = self.push_buffer_view(offset, ARRAY_BUFFER);
+
+        let mut accessor = json!({
+            "bufferView": view,
+            "componentType": FLOAT,
+            "count": data.len(),
+            "type": accessor_type,
+        });
+        if bounds {
+            let mut min = [f32::INFINITY; N];
+            let mut max = [f32::NEG_INFINITY; N];
+            for value in data {
+                for i in 0..N {
+                    min[i] = min[i].min(value[i]);
+                    max[i] = max[i].max(value[i]);
+                }
+            }
+            accessor["min"] = min.into_iter().map(number).collect();
+            accessor["max"] = max.into_iter().map(number).collect();
+        }
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    /// Appends indices and its accessor.
+    /// Returns the accessor index.
+    fn push_indices(&mut self, indices: &[u32]) -> usize {
+        let offset = self.align();
+        for index in indices {
+            self.buffer.extend_from_slice(&index.to_le_bytes());
+        }
+        let view = self.push_buffer_view(offset, ELEMENT_ARRAY_BUFFER);
+
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": UNSIGNED_INT,
+            "count": indices.len(),
+            "type": "SCALAR",
+        }));
+        self.accessors.len() - 1
+    }
+
+    /// Appends the buffer view from `offset` to the end of buffer.
+    /// Returns the buffer view index.
+    fn push_buffer_view(&mut self, offset: usize, target: u32) -> usize {
+        self.buffer_views.push(json!({
+            "buffer": 0,
+            "byteOffset": offset,
+            "byteLength": self.buffer.len() - offset,
+            "target": target,
+        }));
+        self.buffer_views.len() - 1
+    }
+
+    /// Pads the buffer to 4-byte boundary.
+    /// Returns the padded length.
+    fn align(&mut self) -> usize {
+        pad(&mut self.buffer, 0);
+        self.buffer.len()
+    }
+
+    /// Converts the material into PBR metallic-roughness material.
+    fn material(&mut self, material: &Material) -> Value {
+        let diffuse = material.diffuse_color().unwrap_or_else(Vec3::one);
+        let alpha = material.dissolve().unwrap_or(1.0).clamp(0.0, 1.0);
+        let metallic = material.metallic().unwrap_or(0.0);
+        // Same as Blender, when PBR roughness is not defined
+        let roughness = material
+            .roughness()
+            .or_else(|| {
+                material
+                    .specular_intensity()
+                    .map(|ns| 1.0 - (ns.max(0.0) / 1000.0).sqrt())
+            })
+            .unwrap_or(1.0);
+
+        let base_color = [diffuse.x, diffuse.y, diffuse.z, alpha];
+        let mut pbr = json!({
+            "baseColorFactor": base_color.map(|c| number(c.clamp(0.0, 1.0))),
+            "metallicFactor": number(metallic.clamp(0.0, 1.0)),
+            "roughnessFactor": number(roughness.clamp(0.0, 1.0)),
+        });
+        if let Some(map) = material.diffuse_map() {
+            pbr["baseColorTexture"] = self.texture_info(map);
+        }
+
+        let mut json = json!({
+            "name": material.name,
+            "pbrMetallicRoughness": pbr,
+        });
+        if let Some(map) = material.normal_map() {
+            let mut info = self.texture_info(map);
+            if let Some(scale) = map.bump_multiplier {
+                info["scale"] = number(scale);
+            }
+            json["normalTexture"] = info;
+        }
+
+        let emissive_map = material.emissive_map();
+        let emissive = material
+            .emissive_color()
+            .or_else(|| emissive_map.map(|_| Vec3::one()))
+            .filter(|e| *e != Vec3::zero());
+        if let Some(emissive) = emissive {
+            let emissive = emissive.into_array().map(|c| number(c.clamp(0.0, 1.0)));
+            json["emissiveFactor"] = json!(emissive);
+        }
+        if let Some(map) = emissive_map {
+            json["emissiveTexture"] = self.texture_info(map);
+        }
+        if alpha < 1.0 {
+            json["alphaMode"] = "BLEND".into();
+        }
+        json
+    }
+
+    /// Returns the texture info referring the texture map, appending it if not yet.
+    fn texture_info(&mut self, map: &TextureMap) -> Value {
+        if !self.images.contains(&map.path) {
+            self.images.push(map.path.clone());
+        }
+
+        let key = (map.path.clone(), map.clamp);
+        let index = match self.textures.iter().position(|t| *t == key) {
+            Some(i) => i,
+            None => {
+                self.textures.push(key);
+                self.textures.len() - 1
+            }
+        };
+        json!({ "index": index })
+    }
+}
+
+/// Builds the primitive of mesh.
+/// `mode` is omitted for triangles.
+fn primitive(
+    attributes: &Value,
+    indices: usize,
+    material: Option<usize>,
+    mode: Option<u32>,
+) -> Value {
+    compact(json!({
+        "attributes": attributes,
+        "indices": indices,
+        "material": material,
+        "mode": mode,
+    }))
+}
+
+/// Removes null fields of absent values and empty arrays, since glTF disallows empty arrays.
+fn compact(mut value: Value) -> Value {
+    if let Value::Object(fields) = &mut value {
+        fields.retain(|_, v| match v {
+            Value::Null => false,
+            Value::Array(a) => !a.is_empty(),
+            _ => true,
+        });
+    }
+    value
+}
+
+/// Converts the float into JSON number.
+/// JSON can't represent infinity and NaN, so they are written as zero.
+fn number(value: f32) -> Value {
+    if value.is_finite() {
+        value.into()
+    } else {
+        0.into()
+    }
+}
+
+/// Converts the relative path into URI, with `/` separators and percent-encoding.
+fn uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(byte as char)
+            }
+            byte => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// Pads the data to 4-byte boundary.
+fn pad(data: &mut Vec<u8>, padding: u8) {
+    data.resize(data.len().next_multiple_of(4), padding);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Parser, WavefrontObj};
+
+    use std::{io::Cursor, path::Path};
+
+    use serde_json::{json, Value};
+
+    fn parse(source: &str, mtl: &'static str) -> WavefrontObj {
+        let mut parser = Parser::new(move |_, _: &()| Ok(Cursor::new(mtl)));
+        parser.parse(source.as_bytes(), ()).unwrap()
+    }
+
+    fn write_gltf(obj: &WavefrontObj) -> (Value, Vec<u8>) {
+        let mut json = vec![];
+        let mut buffer = vec![];
+        obj.write_gltf(&mut json, &mut buffer, Path::new("quad.bin"))
+            .unwrap();
+        (serde_json::from_slice(&json).unwrap(), buffer)
+    }
+
+    const QUAD: &str = "\
+mtllib quad.mtl
+o quad
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vn 0 0 1
+usemtl red
+f 1/1/1 2/1/1 3/1/1 4/1/1
+l 1 3
+p 2 4
+";
+
+    const MTL: &str = "\
+newmtl red
+Kd 1 0 0
+d 0.5
+map_Kd textures\\red.png
+";
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    /// Reads `SCALAR` unsigned int values of the accessor.
+    fn read_indices(json: &Value, buffer: &[u8], accessor: &Value) -> Vec<u32> {
+        assert_eq!(accessor["componentType"], 5125);
+        assert_eq!(accessor["type"], "SCALAR");
+        let view = &json["bufferViews"][accessor["bufferView"].as_u64().unwrap() as usize];
+        let offset = view["byteOffset"].as_u64().unwrap() as usize;
+        let count = accessor["count"].as_u64().unwrap() as usize;
+        (0..count).map(|i| u32_at(buffer, offset + i * 4)).collect()
+    }
+
+    #[test]
+    fn writes_aligned_glb_chunks() {
+        let mut glb = vec![];
+        parse(QUAD, MTL).write_glb(&mut glb).unwrap();
+
+        assert_eq!(&glb[0..4], b"glTF");
+        assert_eq!(u32_at(&glb, 4), 2);
+        assert_eq!(u32_at(&glb, 8) as usize, glb.len());
+
+        let json_length = u32_at(&glb, 12) as usize;
+        assert_eq!(&glb[16..20], b"JSON");
+        assert_eq!(json_length % 4, 0);
+        let json: Value = serde_json::from_slice(&glb[20..20 + json_length]).unwrap();
+
+        let bin = 20 + json_length;
+        let bin_length = u32_at(&glb, bin) as usize;
+        assert_eq!(&glb[bin + 4..bin + 8], b"BIN\0");
+        assert_eq!(bin_length % 4, 0);
+        assert_eq!(bin + 8 + bin_length, glb.len());
+        assert_eq!(json["buffers"], json!([{ "byteLength": bin_length }]));
+    }
+
+    #[test]
+    fn converts_objects_and_materials() {
+        let (json, buffer) = write_gltf(&parse(QUAD, MTL));
+
+        assert_eq!(
+            json["buffers"],
+            json!([{ "byteLength": buffer.len(), "uri": "quad.bin" }])
+        );
+        assert_eq!(json["scenes"], json!([{ "nodes": [1] }]));
+        assert_eq!(json["nodes"][1], json!({ "name": "quad", "children": [0] }));
+        assert_eq!(json["nodes"][0]["mesh"], 0);
+
+        let material = &json["materials"][0];
+        assert_eq!(material["name"], "red");
+        assert_eq!(
+            material["pbrMetallicRoughness"]["baseColorFactor"],
+            json!([1.0, 0.0, 0.0, 0.5])
+        );
+        assert_eq!(
+            material["pbrMetallicRoughness"]["baseColorTexture"],
+            json!({ "index": 0 })
+        );
+        assert_eq!(material["alphaMode"], "BLEND");
+        assert_eq!(json["textures"], json!([{ "source": 0 }]));
+        assert_eq!(json["images"], json!([{ "uri": "textures/red.png" }]));
+    }
+
+    #[test]
+    fn accessors_refer_buffer_views() {
+        let (json, buffer) = write_gltf(&parse(QUAD, MTL));
+
+        for view in json["bufferViews"].as_array().unwrap() {
+            let offset = view["byteOffset"].as_u64().unwrap() as usize;
+            let length = view["byteLength"].as_u64().unwrap() as usize;
+            assert_eq!(offset % 4, 0);
+            assert!(offset + length <= buffer.len());
+        }
+        for accessor in json["accessors"].as_array().unwrap() {
+            let components = match accessor["type"].as_str().unwrap() {
+                "SCALAR" => 1,
+                "VEC2" => 2,
+                "VEC3" => 3,
+                t => panic!("unexpected type {}", t),
+            };
+            let view = &json["bufferViews"][accessor["bufferView"].as_u64().unwrap() as usize];
+            assert_eq!(
+                accessor["count"].as_u64().unwrap() * components * 4,
+                view["byteLength"].as_u64().unwrap()
+            );
+        }
+
+        let primitives = json["meshes"][0]["primitives"].as_array().unwrap();
+        let modes: Vec<_> = primitives.iter().map(|p| p["mode"].clone()).collect();
+        assert_eq!(modes, [Value::Null, json!(1), json!(0)]);
+
+        let expected = [vec![0, 1, 2, 0, 2, 3], vec![0, 1], vec![2, 3]];
+        for (primitive, indices) in primitives.iter().zip(expected) {
+            assert_eq!(primitive["material"], 0);
+            let attributes = &primitive["attributes"];
+            let position = &json["accessors"][attributes["POSITION"].as_u64().unwrap() as usize];
+            assert_eq!(position["count"], 4);
+            assert_eq!(position["min"], json!([0.0, 0.0, 0.0]));
+            assert_eq!(position["max"], json!([1.0, 1.0, 0.0]));
+
+            let accessor = &json["accessors"][primitive["indices"].as_u64().unwrap() as usize];
+            assert_eq!(read_indices(&json, &buffer, accessor), indices);
+        }
+        assert!(primitives[0]["attributes"]["NORMAL"].is_u64());
+        assert!(primitives[0]["attributes"]["TEXCOORD_0"].is_u64());
+        // Lines and points share vertices without normals
+        assert_eq!(primitives[1]["attributes"], primitives[2]["attributes"]);
+        assert!(primitives[1]["attributes"]["NORMAL"].is_null());
+    }
+
+    #[test]
+    fn writes_empty_model_without_buffer() {
+        let mut glb = vec![];
+        parse("", "").write_glb(&mut glb).unwrap();
+        assert_eq!(glb.len(), 20 + u32_at(&glb, 12) as usize);
+    }
+}
@@ -1,20 +1,50 @@
 //! Parses the Wavefront OBJ format.
 
+#[cfg(feature = "async")]
+mod async_parser;
+mod command;
+mod decimate;
+mod diagnostic;
+mod gltf;
+mod mesh;
 mod mtl;
+mod normals;
 mod obj;
 mod parser;
+mod ply;
+mod resolver;
+mod stl;
+mod tangent;
+mod transform;
+mod triangulate;
+mod validate;
+mod writer;
 
-pub use mtl::{Material, MaterialProperty};
-pub use obj::{Group, Object, FaceVertexPair};
+#[cfg(feature = "async")]
+pub use async_parser::AsyncParser;
+pub use command::{MtlCommand, MtlCommands, ObjCommand, ObjCommands};
+pub use diagnostic::{Diagnostic, DiagnosticKind};
+pub use mesh::{DrawRange, IndexedMesh, MeshVertex};
+pub use mtl::{Material, MaterialProperty, TextureChannel, TextureMap};
+pub use obj::{
+    ElementIndices, FaceIndexPair, FaceIndices, FaceList, FaceListIter, FaceVertexPair, Group,
+    Object,
+};
 pub use parser::Parser;
+pub use resolver::{FileResolver, MemoryResolver, Resolver};
+pub use transform::Axis;
+pub use validate::{RepairOptions, ValidationIssue};
 
 use std::{
     error::Error as StdError,
     fmt::{Display, Formatter, Result as FmtResult},
     io::Error as IoError,
+    path::Path,
     result::Result as StdResult,
 };
 
+use vek::Vec3;
+
 /// Results for Wavefront OBJ/MTL parsing.
 pub type Result<T> = StdResult<T, Error>;
 
@@ -24,20 +54,68 @@ pub enum Error {
     /// Not enough value defined in `v`, `vt`, `vn`, etc.
     NotEnoughData { found: usize, expected: usize },
 
-    /// Invalid `f` definition detected (referencing undefined vertices).
-    InvalidFaceVertex,
+    /// Invalid `f`, `l` or `p` definition detected (referencing undefined vertices).
+    /// Contains the face indices (0-based, counted in whole file) in question.
+    InvalidFaceVertex(Box<[FaceIndexPair]>),
 
-    /// Invalid `f` index detected (zero or negative index).
+    /// Invalid `f` index detected (zero index, or negative index beyond defined elements).
     InvalidIndex,
 
     /// Specified filename was not found.
     PathNotFound(String),
 
+    /// Specified filename is outside of the permitted directory.
+    PathNotAllowed(String),
+
     /// IO error.
     IoError(IoError),
 
+    /// Texture UVs are required but some vertices don't have them.
+    MissingTextureUv,
+
+    /// Normals are required but some vertices don't have them.
+    MissingNormal,
+
+    /// Too many vertices or indices to be referred with `u32` indices.
+    TooManyVertices,
+
+    /// Unsupported or broken file format, such as big-endian PLY.
+    UnsupportedFormat(String),
+
     /// Parsing error.
-    ParseError,
+    /// Contains the token which could not be parsed.
+    ParseError(String),
+
+    /// Problem reported as error in strict mode.
+    Diagnostic(DiagnosticKind),
+
+    /// Error occurred at specific line in OBJ/MTL file.
+    Located {
+        location: Location,
+        error: Box<Error>,
+    },
+}
+
+impl Error {
+    /// The location where this error occurred, if known.
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            Error::Located { location, .. } => Some(location),
+            _ => None,
+        }
+    }
+
+    /// Attaches the location to this error.
+    /// Already located errors are kept as they are.
+    pub(crate) fn at(self, location: Location) -> Error {
+        match self {
+            located @ Error::Located { .. } => located,
+            error => Error::Located {
+                location,
+                error: Box::new(error),
+            },
+        }
+    }
 }
 
 impl Display for Error {
@@ -48,16 +126,108 @@ impl Display for Error {
                 "Not enough data (found {}, expected {})",
                 found, expected
             ),
-            Error::InvalidFaceVertex => write!(f, "Invalid face vertex definition"),
+            Error::InvalidFaceVertex(face) => {
+                write!(f, "Invalid face vertex definition: {:?}", face)
+            }
             Error::InvalidIndex => write!(f, "Invalid index definition"),
             Error::PathNotFound(path) => write!(f, "Path not found: \"{}\"", path),
+            Error::PathNotAllowed(path) => write!(f, "Path not allowed: \"{}\"", path),
             Error::IoError(err) => err.fmt(f),
-            Error::ParseError => write!(f, "Failed to parse a value"),
+            Error::MissingTextureUv => write!(f, "Texture UVs are missing"),
+            Error::MissingNormal => write!(f, "Normals are missing"),
+            Error::TooManyVertices => write!(f, "Too many vertices for 32-bit indices"),
+            Error::UnsupportedFormat(format) => write!(f, "Unsupported format: {}", format),
+            Error::ParseError(token) => write!(f, "Failed to parse a value: \"{}\"", token),
+            Error::Diagnostic(kind) => kind.fmt(f),
+            Error::Located { location, error } => write!(f, "{}: {}", location, error),
         }
     }
 }
 
-impl StdError for Error {}
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::IoError(err) => Some(err),
+            Error::Located { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// Represents which file an error occurred in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SourceFile {
+    /// The OBJ file itself.
+    Obj,
+
+    /// The MTL file specified with `mtllib`.
+    Mtl(Box<Path>),
+
+    /// The STL file.
+    Stl,
+
+    /// The PLY file.
+    Ply,
+}
+
+impl Display for SourceFile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            SourceFile::Obj => write!(f, "OBJ"),
+            SourceFile::Mtl(path) => write!(f, "MTL \"{}\"", path.display()),
+            SourceFile::Stl => write!(f, "STL"),
+            SourceFile::Ply => write!(f, "PLY"),
+        }
+    }
+}
+
+/// Represents the location of a line in OBJ/MTL file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    file: SourceFile,
+    line: usize,
+    text: Box<str>,
+}
+
+impl Location {
+    pub(crate) fn new(file: SourceFile, line: usize, text: &str) -> Location {
+        Location {
+            file,
+            line,
+            text: text.trim().into(),
+        }
+    }
+
+    /// The file which contains the line.
+    pub fn file(&self) -> &SourceFile {
+        &self.file
+    }
+
+    /// The line number (1-based).
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// The keyword of the line.
+    pub fn keyword(&self) -> &str {
+        self.text.split_whitespace().next().unwrap_or("")
+    }
+
+    /// The raw text of the line.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{} line {}", self.file, self.line)?;
+        if !self.text.is_empty() {
+            write!(f, " (\"{}\")", self.text)?;
+        }
+        Ok(())
+    }
+}
 
 impl From<IoError> for Error {
     fn from(err: IoError) -> Self {
@@ -70,6 +240,8 @@ impl From<IoError> for Error {
 pub struct WavefrontObj {
     objects: Box<[Object]>,
     materials: Box<[Material]>,
+    parameter_vertices: Box<[Vec3<f32>]>,
+    diagnostics: Box<[Diagnostic]>,
 }
 
 impl WavefrontObj {
@@ -83,8 +255,85 @@ impl WavefrontObj {
         &self.materials
     }
 
+    /// Parameter space vertices (`vp`) for free-form geometry, as (u, v, w).
+    /// Free-form geometry itself is not supported, so they are kept only to be written.
+    /// Missing v is 0 and missing w is 1.
+    pub fn parameter_vertices(&self) -> &[Vec3<f32>] {
+        &self.parameter_vertices
+    }
+
+    /// Problems found in parsing, which didn't prevent it.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
     /// Splits into separate data, objects and materials.
     pub fn split(self) -> (Box<[Object]>, Box<[Material]>) {
         (self.objects, self.materials)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    fn parse(source: &str) -> Result<WavefrontObj> {
+        let mut parser = Parser::new(|path, _: &()| match path.to_str() {
+            Some("a.mtl") => Ok(Cursor::new("newmtl a\nKd 1 x 0\n")),
+            _ => Err(Error::PathNotFound(path.display().to_string())),
+        });
+        parser.parse(source.as_bytes(), ())
+    }
+
+    fn unlocated(error: &Error) -> &Error {
+        match error {
+            Error::Located { error, .. } => error,
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn obj_errors_have_locations() {
+        let error = parse("v 0 0 0\n\nv 1 0\n").unwrap_err();
+        let location = error.location().unwrap();
+        assert_eq!(location.file(), &SourceFile::Obj);
+        assert_eq!(location.line(), 3);
+        assert_eq!(location.keyword(), "v");
+        assert_eq!(location.text(), "v 1 0");
+        assert!(matches!(
+            unlocated(&error),
+            Error::NotEnoughData {
+                found: 2,
+                expected: 3
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "OBJ line 3 (\"v 1 0\"): Not enough data (found 2, expected 3)"
+        );
+    }
+
+    #[test]
+    fn mtl_errors_have_locations() {
+        let error = parse("mtllib a.mtl\n").unwrap_err();
+        let location = error.location().unwrap();
+        assert_eq!(location.file(), &SourceFile::Mtl(Path::new("a.mtl").into()));
+        assert_eq!(location.line(), 2);
+        assert!(matches!(unlocated(&error), Error::ParseError(token) if token == "x"));
+
+        let error = parse("v 0 0 0\nmtllib b.mtl\n").unwrap_err();
+        assert_eq!(error.location().unwrap().line(), 2);
+        assert!(matches!(unlocated(&error), Error::PathNotFound(_)));
+    }
+
+    #[test]
+    fn io_errors_are_sources() {
+        let location = Location::new(SourceFile::Obj, 1, "v");
+        let error = Error::from(IoError::other("broken")).at(location);
+        let source = error.source().unwrap();
+        assert!(matches!(source.downcast_ref(), Some(Error::IoError(_))));
+        assert_eq!(source.source().unwrap().to_string(), "broken");
+    }
+}
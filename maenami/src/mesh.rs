@@ -0,0 +1,366 @@
+use crate::{
+    obj::{all_share_vertex_data, FaceIndexPair, Group, Object},
+    triangulate::triangulate_polygon,
+    Error, Result,
+};
+
+use std::{collections::HashMap, ops::Range};
+
+use vek::{Rgb, Vec2, Vec3};
+
+/// Represents an interleaved vertex for GPU buffers.
+/// Missing UVs and normals are filled with zero.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct MeshVertex {
+    /// The position.
+    pub position: [f32; 3],
+
+    /// The texture UV.
+    pub uv: [f32; 2],
+
+    /// The normal.
+    pub normal: [f32; 3],
+}
+
+/// Represents a range in index buffer drawn with single material.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DrawRange {
+    material: Option<usize>,
+    indices: Range<u32>,
+}
+
+impl DrawRange {
+    /// The material index in `WavefrontObj::materials()`.
+    pub fn material(&self) -> Option<usize> {
+        self.material
+    }
+
+    /// The range in index buffer.
+    pub fn indices(&self) -> Range<u32> {
+        self.indices.clone()
+    }
+}
+
+/// Represents a triangle list with vertex buffer and index buffer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedMesh {
+    pub(crate) vertices: Box<[MeshVertex]>,
+    pub(crate) indices: Box<[u32]>,
+    pub(crate) draw_ranges: Box<[DrawRange]>,
+    pub(crate) colors: Option<Box<[[f32; 3]]>>,
+    pub(crate) tangents: Option<Box<[[f32; 4]]>>,
+    pub(crate) has_uvs: bool,
+    pub(crate) has_normals: bool,
+}
+
+impl IndexedMesh {
+    /// The vertex buffer.
+    pub fn vertices(&self) -> &[MeshVertex] {
+        &self.vertices
+    }
+
+    /// The index buffer.
+    /// Each three indices form a triangle.
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    /// The ranges in index buffer for each material.
+    /// Ranges are ordered by first appearance of the material.
+    pub fn draw_ranges(&self) -> &[DrawRange] {
+        &self.draw_ranges
+    }
+
+    /// The vertex colors corresponding to `vertices()`.
+    /// Returns `None` if the source has no vertex colors.
+    pub fn colors(&self) -> Option<&[[f32; 3]]> {
+        self.colors.as_deref()
+    }
+
+    /// The tangents corresponding to `vertices()`.
+    /// Returns `None` unless generated with `generate_tangents`.
+    pub fn tangents(&self) -> Option<&[[f32; 4]]> {
+        self.tangents.as_deref()
+    }
+
+    /// Whether all vertices have texture UVs.
+    pub fn has_uvs(&self) -> bool {
+        self.has_uvs
+    }
+
+    /// Whether all vertices have normals.
+    pub fn has_normals(&self) -> bool {
+        self.has_normals
+    }
+}
+
+impl Group {
+    /// Converts into indexed triangle list.
+    /// Identical `(v, vt, vn)` combinations share the same vertex.
+    /// Fails with `Error::TooManyVertices` if `u32` indices are not enough.
+    pub fn to_indexed_mesh(&self) -> Result<IndexedMesh> {
+        let mut builder = MeshBuilder::default();
+        builder.push_group(self)?;
+        builder.build()
+    }
+}
+
+impl Object {
+    /// Converts all groups into single indexed triangle list.
+    /// Identical `(v, vt, vn)` combinations share the same vertex, assuming
+    /// groups share vertex data as parsed from single file.
+    /// Fails with `Error::TooManyVertices` if `u32` indices are not enough.
+    pub fn to_indexed_mesh(&self) -> Result<IndexedMesh> {
+        debug_assert!(
+            all_share_vertex_data(self.groups.iter()),
+            "Groups should share vertex data"
+        );
+        let mut builder = MeshBuilder::default();
+        for group in self.groups.iter() {
+            builder.push_group(group)?;
+        }
+        builder.build()
+    }
+}
+
+/// Collects triangles and deduplicates vertices.
+#[derive(Debug)]
+struct MeshBuilder {
+    vertices: Vec<MeshVertex>,
+    colors: Vec<[f32; 3]>,
+    has_colors: bool,
+    vertex_indices: HashMap<FaceIndexPair, u32>,
+    triangles: Vec<(Option<usize>, Vec<u32>)>,
+    missing_uvs: bool,
+    missing_normals: bool,
+    max_index: u32,
+}
+
+impl Default for MeshBuilder {
+    fn default() -> MeshBuilder {
+        MeshBuilder {
+            vertices: vec![],
+            colors: vec![],
+            has_colors: false,
+            vertex_indices: HashMap::new(),
+            triangles: vec![],
+            missing_uvs: false,
+            missing_normals: false,
+            max_index: u32::MAX,
+        }
+    }
+}
+
+impl MeshBuilder {
+    /// Triangulates and appends all faces in the group.
+    fn push_group(&mut self, group: &Group) -> Result<()> {
+        self.has_colors |= group.colors.is_some();
+        for (face, material, _) in group.face_index_pairs.iter() {
+            let positions: Vec<_> = face.iter().map(|p| group.vertices[p.0]).collect();
+            let triangles = triangulate_polygon(&positions);
+            if triangles.is_empty() {
+                continue;
+            }
+
+            let indices = face
+                .iter()
+                .map(|p| self.vertex_index(group, *p))
+                .collect::<Result<Vec<_>>>()?;
+            let list = self.material_list(material);
+            for [a, b, c] in triangles {
+                list.extend_from_slice(&[indices[a], indices[b], indices[c]]);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the index of the vertex, inserting it if not yet.
+    fn vertex_index(&mut self, group: &Group, pair: FaceIndexPair) -> Result<u32> {
+        self.missing_uvs |= pair.1.is_none();
+        self.missing_normals |= pair.2.is_none();
+        if let Some(&index) = self.vertex_indices.get(&pair) {
+            return Ok(index);
+        }
+
+        let index = u32::try_from(self.vertices.len())
+            .ok()
+            .filter(|&i| i <= self.max_index)
+            .ok_or(Error::TooManyVertices)?;
+        let FaceIndexPair(v, t, n) = pair;
+        let uv = t.map_or(Vec2::zero(), |i| group.texture_uvs[i]);
+        let normal = n.map_or(Vec3::zero(), |i| group.normals[i]);
+        self.vertices.push(MeshVertex {
+            position: group.vertices[v].into_array(),
+            uv: uv.into_array(),
+            normal: normal.into_array(),
+        });
+        let color = group.colors.as_ref().map_or(Rgb::white(), |c| c[v]);
+        self.colors.push(color.into_array());
+        self.vertex_indices.insert(pair, index);
+        Ok(index)
+    }
+
+    /// Returns the index list for the material.
+    fn material_list(&mut self, material: Option<usize>) -> &mut Vec<u32> {
+        let position = match self.triangles.iter().position(|(m, _)| *m == material) {
+            Some(p) => p,
+            None => {
+                self.triangles.push((material, vec![]));
+                self.triangles.len() - 1
+            }
+        };
+        &mut self.triangles[position].1
+    }
+
+    /// Concatenates index lists.
+    fn build(self) -> Result<IndexedMesh> {
+        let offset =
+            |indices: &Vec<u32>| u32::try_from(indices.len()).map_err(|_| Error::TooManyVertices);
+        let mut indices = vec![];
+        let mut draw_ranges = vec![];
+        for (material, list) in self.triangles {
+            let start = offset(&indices)?;
+            indices.extend(list);
+            draw_ranges.push(DrawRange {
+                material,
+                indices: start..offset(&indices)?,
+            });
+        }
+
+        Ok(IndexedMesh {
+            vertices: self.vertices.into_boxed_slice(),
+            indices: indices.into_boxed_slice(),
+            draw_ranges: draw_ranges.into_boxed_slice(),
+            colors: self.has_colors.then(|| self.colors.into_boxed_slice()),
+            tangents: None,
+            has_uvs: !self.missing_uvs,
+            has_normals: !self.missing_normals,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parser, WavefrontObj};
+
+    use std::io::Cursor;
+
+    fn parse(source: &str) -> WavefrontObj {
+        let mut parser = Parser::new(|_, _: &()| Ok(Cursor::new(b"newmtl red\nnewmtl blue\n")));
+        parser.parse(source.as_bytes(), ()).unwrap()
+    }
+
+    fn ranges(mesh: &IndexedMesh) -> Vec<(Option<usize>, Range<u32>)> {
+        mesh.draw_ranges()
+            .iter()
+            .map(|r| (r.material(), r.indices()))
+            .collect()
+    }
+
+    #[test]
+    fn identical_index_pairs_share_vertex() {
+        let source = "\
+mtllib a.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 1
+usemtl red
+f 1/1 2/1 3/1 4/1
+usemtl blue
+f 1/1 3/1 4/2
+";
+        let mesh = parse(source).objects()[0].to_indexed_mesh().unwrap();
+
+        assert_eq!(mesh.vertices().len(), 5);
+        assert_eq!(mesh.indices().len(), 9);
+        assert!(mesh.has_uvs());
+        assert!(!mesh.has_normals());
+        assert_eq!(mesh.colors(), None);
+
+        assert_eq!(ranges(&mesh), [(Some(0), 0..6), (Some(1), 6..9)]);
+        assert_eq!(mesh.indices()[6..], [0, 2, 4]);
+        assert_eq!(mesh.vertices()[4].uv, [1.0, 1.0]);
+    }
+
+    #[test]
+    fn faces_are_grouped_by_material() {
+        let source = "\
+mtllib a.mtl
+v 0 0 0
+v 1 0 0
+v 0 1 0
+f 1 2 3
+usemtl red
+f 1 2 3
+usemtl blue
+f 1 3 2
+usemtl red
+f 3 2 1
+usemtl green
+f 2 1 3
+";
+        let mesh = parse(source).objects()[0].to_indexed_mesh().unwrap();
+        assert_eq!(mesh.vertices().len(), 3);
+        assert_eq!(
+            ranges(&mesh),
+            [(None, 0..6), (Some(0), 6..12), (Some(1), 12..15)]
+        );
+        assert_eq!(mesh.indices()[..6], [0, 1, 2, 1, 0, 2]);
+        assert_eq!(mesh.indices()[6..12], [0, 1, 2, 2, 1, 0]);
+    }
+
+    #[test]
+    fn vertices_are_shared_across_groups() {
+        let source = "\
+v 0 0 0
+v 1 0 0
+v 0 1 0
+v 1 1 0
+vt 0 0
+g a
+f 1/1 2/1 3/1
+g b
+f 3/1 2/1 1/1
+f 2/1 4/1 3/1
+";
+        let obj = parse(source);
+        let object = &obj.objects()[0];
+        assert_eq!(object.groups().len(), 2);
+
+        let mesh = object.to_indexed_mesh().unwrap();
+        assert_eq!(mesh.vertices().len(), 4);
+        assert_eq!(mesh.indices(), [0, 1, 2, 2, 1, 0, 1, 3, 2]);
+        assert_eq!(ranges(&mesh), [(None, 0..9)]);
+
+        let group_mesh = object.groups()[1].to_indexed_mesh().unwrap();
+        assert_eq!(group_mesh.vertices().len(), 4);
+        assert_eq!(group_mesh.indices(), [0, 1, 2, 1, 3, 0]);
+    }
+
+    #[test]
+    fn too_many_vertices_fail() {
+        let obj = parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n");
+        let group = &obj.objects()[0].groups()[0];
+
+        let mut builder = MeshBuilder {
+            max_index: 3,
+            ..Default::default()
+        };
+        builder.push_group(group).unwrap();
+        assert_eq!(builder.build().unwrap().vertices().len(), 4);
+
+        let mut builder = MeshBuilder {
+            max_index: 2,
+            ..Default::default()
+        };
+        assert!(matches!(
+            builder.push_group(group),
+            Err(Error::TooManyVertices)
+        ));
+    }
+}
@@ -1,4 +1,9 @@
-use std::{collections::HashMap, path::Path};
+use crate::WavefrontObj;
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 use vek::Vec3;
 
@@ -13,12 +18,149 @@ pub enum MaterialProperty {
     Integer(u32),
 
     /// Vector value.
-    /// Property name starts with `K`.
+    /// Property name starts with `K`, or is `Tf`.
     Vector(Vec3<f32>),
 
-    /// Path value.
-    /// Property name starts with `map_`.
-    Path(Box<Path>),
+    /// Spectral curve file and its multiplier, defined with `spectral` form.
+    Spectral(Box<Path>, f32),
+
+    /// CIE XYZ color, defined with `xyz` form.
+    Xyz(Vec3<f32>),
+
+    /// Dissolve value, and whether `-halo` is specified.
+    /// Property name is `d`.
+    Dissolve(f32, bool),
+
+    /// Texture map value.
+    /// Property name starts with `map_`, or is one of `bump`, `disp`, `decal`, `refl` and `norm`.
+    /// Since 0.2, this replaces `Path` of 0.1; the path is `TextureMap::path`.
+    Texture(TextureMap),
+}
+
+/// Represents the channel used for scalar texture (`-imfchan`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureChannel {
+    /// `r`
+    Red,
+
+    /// `g`
+    Green,
+
+    /// `b`
+    Blue,
+
+    /// `m`
+    Matte,
+
+    /// `l`
+    Luminance,
+
+    /// `z`
+    Depth,
+}
+
+/// Represents a texture map and its options.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureMap {
+    pub(crate) path: Box<Path>,
+    pub(crate) origin: Vec3<f32>,
+    pub(crate) scale: Vec3<f32>,
+    pub(crate) turbulence: Vec3<f32>,
+    pub(crate) bump_multiplier: Option<f32>,
+    pub(crate) clamp: bool,
+    pub(crate) blend_u: bool,
+    pub(crate) blend_v: bool,
+    pub(crate) color_correction: bool,
+    pub(crate) value_range: (f32, f32),
+    pub(crate) channel: Option<TextureChannel>,
+    pub(crate) resolution: Option<u32>,
+    pub(crate) map_type: Option<Box<str>>,
+}
+
+impl TextureMap {
+    /// Creates a texture map with default options.
+    pub fn new(path: impl Into<Box<Path>>) -> TextureMap {
+        TextureMap {
+            path: path.into(),
+            origin: Vec3::zero(),
+            scale: Vec3::one(),
+            turbulence: Vec3::zero(),
+            bump_multiplier: None,
+            clamp: false,
+            blend_u: true,
+            blend_v: true,
+            color_correction: false,
+            value_range: (0.0, 1.0),
+            channel: None,
+            resolution: None,
+            map_type: None,
+        }
+    }
+
+    /// The path of texture file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The texture origin offset, which is defined with `-o`.
+    pub fn origin(&self) -> Vec3<f32> {
+        self.origin
+    }
+
+    /// The texture scale, which is defined with `-s`.
+    pub fn scale(&self) -> Vec3<f32> {
+        self.scale
+    }
+
+    /// The turbulence, which is defined with `-t`.
+    pub fn turbulence(&self) -> Vec3<f32> {
+        self.turbulence
+    }
+
+    /// The bump multiplier, which is defined with `-bm`.
+    pub fn bump_multiplier(&self) -> Option<f32> {
+        self.bump_multiplier
+    }
+
+    /// Whether the texture is clamped, which is defined with `-clamp`.
+    pub fn clamp(&self) -> bool {
+        self.clamp
+    }
+
+    /// Whether horizontal blending is enabled, which is defined with `-blendu`.
+    pub fn blend_u(&self) -> bool {
+        self.blend_u
+    }
+
+    /// Whether vertical blending is enabled, which is defined with `-blendv`.
+    pub fn blend_v(&self) -> bool {
+        self.blend_v
+    }
+
+    /// Whether color correction is enabled, which is defined with `-cc`.
+    pub fn color_correction(&self) -> bool {
+        self.color_correction
+    }
+
+    /// The base value and gain, which is defined with `-mm`.
+    pub fn value_range(&self) -> (f32, f32) {
+        self.value_range
+    }
+
+    /// The channel used for scalar texture, which is defined with `-imfchan`.
+    pub fn channel(&self) -> Option<TextureChannel> {
+        self.channel
+    }
+
+    /// The texture resolution, which is defined with `-texres`.
+    pub fn resolution(&self) -> Option<u32> {
+        self.resolution
+    }
+
+    /// The reflection map type, which is defined with `-type`.
+    pub fn map_type(&self) -> Option<&str> {
+        self.map_type.as_deref()
+    }
 }
 
 /// Represents a material defined in MTL file.
@@ -36,36 +178,93 @@ impl Material {
 
     /// The ambient color, which is defined with `Ka`.
     pub fn ambient_color(&self) -> Option<Vec3<f32>> {
-        match self.properties.get("Ka") {
-            Some(MaterialProperty::Vector(v)) => Some(*v),
-            _ => None,
-        }
+        self.vector("Ka")
     }
 
     /// The diffuse color, which is defined with `Kd`.
     pub fn diffuse_color(&self) -> Option<Vec3<f32>> {
-        match self.properties.get("Kd") {
-            Some(MaterialProperty::Vector(v)) => Some(*v),
-            _ => None,
-        }
+        self.vector("Kd")
     }
 
     /// The specular color, which is defined with `Ks`.
     pub fn specular_color(&self) -> Option<Vec3<f32>> {
-        match self.properties.get("Ks") {
-            Some(MaterialProperty::Vector(v)) => Some(*v),
-            _ => None,
-        }
+        self.vector("Ks")
+    }
+
+    /// The emissive color, which is defined with `Ke`.
+    pub fn emissive_color(&self) -> Option<Vec3<f32>> {
+        self.vector("Ke")
+    }
+
+    /// The transmission filter, which is defined with `Tf`.
+    /// Like other colors, `xyz` form is converted into linear sRGB, and `spectral` form
+    /// returns `None`; use `get` for the curve file.
+    pub fn transmission_filter(&self) -> Option<Vec3<f32>> {
+        self.vector("Tf")
     }
 
     /// The specular intensity, which is defined with `Ns`.
     pub fn specular_intensity(&self) -> Option<f32> {
-        match self.properties.get("Ns") {
-            Some(MaterialProperty::Float(v)) => Some(*v),
-            _ => None,
+        self.float("Ns")
+    }
+
+    /// The optical density (index of refraction), which is defined with `Ni`.
+    pub fn optical_density(&self) -> Option<f32> {
+        self.float("Ni")
+    }
+
+    /// The dissolve (opacity), which is defined with `d`.
+    /// If only `Tr` is defined, returns `1 - Tr`.
+    pub fn dissolve(&self) -> Option<f32> {
+        match self.properties.get("d") {
+            Some(MaterialProperty::Dissolve(v, _)) => Some(*v),
+            _ => self.float("Tr").map(|tr| 1.0 - tr),
         }
     }
 
+    /// Whether the dissolve depends on the surface orientation, which is defined with `d -halo`.
+    pub fn dissolve_halo(&self) -> bool {
+        matches!(
+            self.properties.get("d"),
+            Some(MaterialProperty::Dissolve(_, true))
+        )
+    }
+
+    /// The PBR roughness, which is defined with `Pr`.
+    pub fn roughness(&self) -> Option<f32> {
+        self.float("Pr")
+    }
+
+    /// The PBR metallic, which is defined with `Pm`.
+    pub fn metallic(&self) -> Option<f32> {
+        self.float("Pm")
+    }
+
+    /// The PBR sheen, which is defined with `Ps`.
+    pub fn sheen(&self) -> Option<f32> {
+        self.float("Ps")
+    }
+
+    /// The PBR clearcoat thickness, which is defined with `Pc`.
+    pub fn clearcoat(&self) -> Option<f32> {
+        self.float("Pc")
+    }
+
+    /// The PBR clearcoat roughness, which is defined with `Pcr`.
+    pub fn clearcoat_roughness(&self) -> Option<f32> {
+        self.float("Pcr")
+    }
+
+    /// The anisotropy, which is defined with `aniso`.
+    pub fn anisotropy(&self) -> Option<f32> {
+        self.float("aniso")
+    }
+
+    /// The anisotropy rotation, which is defined with `anisor`.
+    pub fn anisotropy_rotation(&self) -> Option<f32> {
+        self.float("anisor")
+    }
+
     /// The illumination type, which is defined with `illum`.
     pub fn illumination(&self) -> Option<u32> {
         match self.properties.get("illum") {
@@ -74,10 +273,102 @@ impl Material {
         }
     }
 
+    /// The ambient map, which is defined with `map_Ka`.
+    pub fn ambient_map(&self) -> Option<&TextureMap> {
+        self.texture("map_Ka")
+    }
+
     /// The diffuse map, which is defined with `map_Kd`.
-    pub fn diffuse_map(&self) -> Option<&Path> {
-        match self.properties.get("map_Kd") {
-            Some(MaterialProperty::Path(v)) => Some(v),
+    /// Since 0.2, this returns the texture map with options instead of the path;
+    /// use `TextureMap::path` for the path.
+    pub fn diffuse_map(&self) -> Option<&TextureMap> {
+        self.texture("map_Kd")
+    }
+
+    /// The specular map, which is defined with `map_Ks`.
+    pub fn specular_map(&self) -> Option<&TextureMap> {
+        self.texture("map_Ks")
+    }
+
+    /// The specular intensity map, which is defined with `map_Ns`.
+    pub fn specular_intensity_map(&self) -> Option<&TextureMap> {
+        self.texture("map_Ns")
+    }
+
+    /// The dissolve map, which is defined with `map_d`.
+    pub fn dissolve_map(&self) -> Option<&TextureMap> {
+        self.texture("map_d")
+    }
+
+    /// The bump map, which is defined with `bump` or `map_bump`.
+    pub fn bump_map(&self) -> Option<&TextureMap> {
+        self.texture("bump")
+            .or_else(|| self.texture("map_bump"))
+            .or_else(|| self.texture("map_Bump"))
+    }
+
+    /// The normal map, which is defined with `norm`.
+    pub fn normal_map(&self) -> Option<&TextureMap> {
+        self.texture("norm")
+    }
+
+    /// The displacement map, which is defined with `disp`.
+    pub fn displacement_map(&self) -> Option<&TextureMap> {
+        self.texture("disp")
+    }
+
+    /// The decal map, which is defined with `decal`.
+    pub fn decal_map(&self) -> Option<&TextureMap> {
+        self.texture("decal")
+    }
+
+    /// The reflection map, which is defined with `refl`.
+    pub fn reflection_map(&self) -> Option<&TextureMap> {
+        self.texture("refl")
+    }
+
+    /// The emissive map, which is defined with `map_Ke`.
+    pub fn emissive_map(&self) -> Option<&TextureMap> {
+        self.texture("map_Ke")
+    }
+
+    /// The PBR roughness map, which is defined with `map_Pr`.
+    pub fn roughness_map(&self) -> Option<&TextureMap> {
+        self.texture("map_Pr")
+    }
+
+    /// The PBR metallic map, which is defined with `map_Pm`.
+    pub fn metallic_map(&self) -> Option<&TextureMap> {
+        self.texture("map_Pm")
+    }
+
+    /// The PBR sheen map, which is defined with `map_Ps`.
+    pub fn sheen_map(&self) -> Option<&TextureMap> {
+        self.texture("map_Ps")
+    }
+
+    /// Returns defined float value with specified key.
+    fn float(&self, key: &str) -> Option<f32> {
+        match self.properties.get(key) {
+            Some(MaterialProperty::Float(v)) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Returns defined RGB value with specified key.
+    /// CIE XYZ color is converted into linear sRGB.
+    fn vector(&self, key: &str) -> Option<Vec3<f32>> {
+        match self.properties.get(key) {
+            Some(MaterialProperty::Vector(v)) => Some(*v),
+            Some(MaterialProperty::Xyz(v)) => Some(xyz_to_rgb(*v)),
+            _ => None,
+        }
+    }
+
+    /// Returns defined texture map with specified key.
+    pub fn texture(&self, key: &str) -> Option<&TextureMap> {
+        match self.properties.get(key) {
+            Some(MaterialProperty::Texture(v)) => Some(v),
             _ => None,
         }
     }
@@ -86,4 +377,202 @@ impl Material {
     pub fn get(&self, key: &str) -> Option<&MaterialProperty> {
         self.properties.get(key)
     }
+
+    /// Iterates all defined properties in arbitrary order.
+    pub fn properties(&self) -> impl Iterator<Item = (&str, &MaterialProperty)> {
+        self.properties.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// Maps paths of texture maps and spectral curve files.
+    /// Useful to rebase relative paths when writing into another directory.
+    pub fn map_paths(&self, mut f: impl FnMut(&Path) -> PathBuf) -> Material {
+        let properties = self
+            .properties
+            .iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    MaterialProperty::Spectral(path, factor) => {
+                        MaterialProperty::Spectral(f(path).into_boxed_path(), *factor)
+                    }
+                    MaterialProperty::Texture(texture_map) => {
+                        MaterialProperty::Texture(TextureMap {
+                            path: f(&texture_map.path).into_boxed_path(),
+                            ..texture_map.clone()
+                        })
+                    }
+                    other => other.clone(),
+                };
+                (key.clone(), value)
+            })
+            .collect();
+
+        Material {
+            name: self.name.clone(),
+            properties,
+        }
+    }
+}
+
+impl WavefrontObj {
+    /// Maps paths of texture maps and spectral curve files in all materials.
+    /// See `Material::map_paths` for details.
+    pub fn map_paths(&self, mut f: impl FnMut(&Path) -> PathBuf) -> WavefrontObj {
+        WavefrontObj {
+            objects: self.objects.clone(),
+            materials: self.materials.iter().map(|m| m.map_paths(&mut f)).collect(),
+            parameter_vertices: self.parameter_vertices.clone(),
+            diagnostics: self.diagnostics.clone(),
+        }
+    }
+}
+
+/// Converts CIE XYZ color into linear sRGB (D65 white point).
+fn xyz_to_rgb(xyz: Vec3<f32>) -> Vec3<f32> {
+    Vec3::new(
+        3.240_454 * xyz.x - 1.537_139 * xyz.y - 0.498_531 * xyz.z,
+        -0.969_266 * xyz.x + 1.876_011 * xyz.y + 0.041_556 * xyz.z,
+        0.055_643 * xyz.x - 0.204_026 * xyz.y + 1.057_225 * xyz.z,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Parser;
+
+    use std::io::Cursor;
+
+    const MTL: &str = "\
+newmtl metal
+Kd 0.5 0.5 0.5
+Ka spectral sky.rfl 0.5
+Ns 10
+illum 2
+map_Kd -s 2 2 2 textures/metal.png
+";
+
+    fn material() -> Material {
+        let mut parser = Parser::new(|_, _: &()| Ok(Cursor::new(MTL)));
+        let obj = parser.parse(&b"mtllib a.mtl\n"[..], ()).unwrap();
+        obj.materials()[0].clone()
+    }
+
+    #[test]
+    fn properties_iterate_all_definitions() {
+        let material = material();
+        let mut properties: Vec<_> = material.properties().collect();
+        properties.sort_by_key(|(k, _)| *k);
+
+        let keys: Vec<_> = properties.iter().map(|(k, _)| *k).collect();
+        assert_eq!(keys, ["Ka", "Kd", "Ns", "illum", "map_Kd"]);
+        assert_eq!(
+            properties[0].1,
+            &MaterialProperty::Spectral(Path::new("sky.rfl").into(), 0.5)
+        );
+        assert_eq!(properties[2].1, &MaterialProperty::Float(10.0));
+        assert_eq!(properties[3].1, &MaterialProperty::Integer(2));
+        for (key, value) in properties {
+            assert_eq!(material.get(key), Some(value));
+        }
+    }
+
+    #[test]
+    fn paths_are_mapped() {
+        let material = material().map_paths(|p| Path::new("..").join(p));
+        assert_eq!(
+            material.get("Ka"),
+            Some(&MaterialProperty::Spectral(
+                Path::new("../sky.rfl").into(),
+                0.5
+            ))
+        );
+        let texture_map = material.diffuse_map().unwrap();
+        assert_eq!(texture_map.path(), Path::new("../textures/metal.png"));
+        assert_eq!(texture_map.scale(), Vec3::broadcast(2.0));
+        assert_eq!(material.diffuse_color(), Some(Vec3::broadcast(0.5)));
+    }
+
+    #[test]
+    fn transparency_emission_and_pbr_are_typed() {
+        let mtl = "\
+newmtl pbr
+d -halo 0.25
+Ke 1 0.5 0
+Tf xyz 0.5 0.5 0.5
+Pr 0.1
+Pm 0.2
+Ps 0.3
+Pc 0.4
+Pcr 0.5
+aniso 0.6
+anisor 0.7
+newmtl glass
+Tr 0.75
+";
+        let mut parser = Parser::new(move |_, _: &()| Ok(Cursor::new(mtl)));
+        let obj = parser.parse(&b"mtllib a.mtl\n"[..], ()).unwrap();
+        let (pbr, glass) = (&obj.materials()[0], &obj.materials()[1]);
+
+        assert_eq!(pbr.dissolve(), Some(0.25));
+        assert!(pbr.dissolve_halo());
+        assert_eq!(pbr.get("d"), Some(&MaterialProperty::Dissolve(0.25, true)));
+        assert_eq!(pbr.properties().count(), 10);
+        assert_eq!(pbr.emissive_color(), Some(Vec3::new(1.0, 0.5, 0.0)));
+        assert_eq!(
+            pbr.get("Tf"),
+            Some(&MaterialProperty::Xyz(Vec3::broadcast(0.5)))
+        );
+        assert_eq!(pbr.roughness(), Some(0.1));
+        assert_eq!(pbr.metallic(), Some(0.2));
+        assert_eq!(pbr.sheen(), Some(0.3));
+        assert_eq!(pbr.clearcoat(), Some(0.4));
+        assert_eq!(pbr.clearcoat_roughness(), Some(0.5));
+        assert_eq!(pbr.anisotropy(), Some(0.6));
+        assert_eq!(pbr.anisotropy_rotation(), Some(0.7));
+
+        assert_eq!(glass.dissolve(), Some(0.25));
+        assert!(!glass.dissolve_halo());
+    }
+
+    #[test]
+    fn transmission_filter_converts_xyz() {
+        let mtl = "\
+newmtl white
+Tf xyz 0.95047 1 1.08883
+newmtl gray
+Tf xyz 0.5
+newmtl rgb
+Tf 0.5 0.25 0
+newmtl curve
+Tf spectral glass.rfl 2
+";
+        let mut parser = Parser::new(move |_, _: &()| Ok(Cursor::new(mtl)));
+        let obj = parser.parse(&b"mtllib a.mtl\n"[..], ()).unwrap();
+        let filters: Vec<_> = obj
+            .materials()
+            .iter()
+            .map(|m| m.transmission_filter())
+            .collect();
+
+        let white = filters[0].unwrap();
+        assert!((white - Vec3::one()).map(f32::abs).reduce_partial_max() < 1e-4);
+        let gray = filters[1].unwrap();
+        assert!(
+            (gray - Vec3::new(0.602_392, 0.474_150, 0.454_421))
+                .map(f32::abs)
+                .reduce_partial_max()
+                < 1e-4
+        );
+        assert_eq!(filters[2], Some(Vec3::new(0.5, 0.25, 0.0)));
+
+        assert_eq!(filters[3], None);
+        assert_eq!(
+            obj.materials()[3].get("Tf"),
+            Some(&MaterialProperty::Spectral(
+                Path::new("glass.rfl").into(),
+                2.0
+            ))
+        );
+    }
 }
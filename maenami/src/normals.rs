@@ -0,0 +1,220 @@
+use crate::{
+    obj::{all_share_vertex_data, FaceIndexPair, FaceList, Group, Object},
+    triangulate::polygon_normal,
+    WavefrontObj,
+};
+
+use std::{collections::HashMap, sync::Arc};
+
+use vek::Vec3;
+
+impl WavefrontObj {
+    /// Generates normals for faces without normal indices in all objects.
+    /// See `Group::generate_normals` for details.
+    pub fn generate_normals(&self) -> WavefrontObj {
+        let groups: Vec<_> = self.objects.iter().flat_map(|o| o.groups.iter()).collect();
+        let mut generated = generate_normals(&groups).into_iter();
+
+        let objects = self
+            .objects
+            .iter()
+            .map(|o| Object {
+                name: o.name.clone(),
+                groups: generated.by_ref().take(o.groups.len()).collect(),
+            })
+            .collect();
+
+        WavefrontObj {
+            objects,
+            materials: self.materials.clone(),
+            parameter_vertices: self.parameter_vertices.clone(),
+            diagnostics: self.diagnostics.clone(),
+        }
+    }
+}
+
+impl Object {
+    /// Generates normals for faces without normal indices in all groups.
+    /// See `Group::generate_normals` for details.
+    pub fn generate_normals(&self) -> Object {
+        let groups: Vec<_> = self.groups.iter().collect();
+        Object {
+            name: self.name.clone(),
+            groups: generate_normals(&groups).into_boxed_slice(),
+        }
+    }
+}
+
+impl Group {
+    /// Generates normals for faces without normal indices.
+    /// Faces with smoothing off get flat normals, and faces in smoothing groups
+    /// get smooth normals weighted by face area and corner angle.
+    /// Existing normal indices are kept as they are.
+    pub fn generate_normals(&self) -> Group {
+        generate_normals(&[self])
+            .pop()
+            .expect("Should return one group")
+    }
+}
+
+/// Generates normals for groups sharing vertex data.
+/// Generated normals are appended to the shared normals, so that the result
+/// groups share them again.
+fn generate_normals(groups: &[&Group]) -> Vec<Group> {
+    debug_assert!(
+        all_share_vertex_data(groups.iter().copied()),
+        "Groups should share vertex data"
+    );
+    let (vertices, mut normals) = match groups.first() {
+        Some(g) => (&g.vertices, g.normals.to_vec()),
+        None => return vec![],
+    };
+
+    // Accumulates weighted normals for each (smoothing group, vertex)
+    let mut smooth_sums: HashMap<(u32, usize), Vec3<f32>> = HashMap::new();
+    for (face, _, smoothing) in groups.iter().flat_map(|g| g.face_index_pairs.iter()) {
+        let smoothing = match smoothing {
+            Some(s) => s,
+            None => continue,
+        };
+
+        let positions: Vec<_> = face.iter().map(|p| vertices[p.0]).collect();
+        let face_normal = polygon_normal(&positions);
+        for (i, pair) in face.iter().enumerate() {
+            let weight = corner_angle(&positions, i);
+            *smooth_sums.entry((smoothing, pair.0)).or_default() += face_normal * weight;
+        }
+    }
+
+    let mut smooth_indices: HashMap<(u32, usize), usize> = HashMap::new();
+    let mut generated_groups = vec![];
+    for group in groups {
+        let mut face_index_pairs = FaceList::default();
+        let mut generated_face = vec![];
+        for (face, material, smoothing) in group.face_index_pairs.iter() {
+            if face.iter().all(|p| p.2.is_some()) {
+                face_index_pairs.push(face, material, smoothing);
+                continue;
+            }
+
+            let positions: Vec<_> = face.iter().map(|p| vertices[p.0]).collect();
+            let face_normal = polygon_normal(&positions)
+                .try_normalized()
+                .unwrap_or_else(Vec3::zero);
+            let mut flat_index = None;
+            generated_face.clear();
+            for &FaceIndexPair(v, t, n) in face.iter() {
+                let n = match (n, smoothing) {
+                    (Some(n), _) => n,
+                    (None, Some(s)) => *smooth_indices.entry((s, v)).or_insert_with(|| {
+                        let sum = smooth_sums[&(s, v)];
+                        normals.push(sum.try_normalized().unwrap_or(face_normal));
+                        normals.len() - 1
+                    }),
+                    (None, None) => *flat_index.get_or_insert_with(|| {
+                        normals.push(face_normal);
+                        normals.len() - 1
+                    }),
+                };
+                generated_face.push(FaceIndexPair(v, t, Some(n)));
+            }
+            face_index_pairs.push(&generated_face, material, smoothing);
+        }
+
+        generated_groups.push(face_index_pairs);
+    }
+
+    let normals: Arc<[_]> = normals.into();
+    groups
+        .iter()
+        .zip(generated_groups)
+        .map(|(group, face_index_pairs)| Group {
+            name: group.name.clone(),
+            names: group.names.clone(),
+            vertices: group.vertices.clone(),
+            texture_uvs: group.texture_uvs.clone(),
+            normals: normals.clone(),
+            colors: group.colors.clone(),
+            vertex_ws: group.vertex_ws.clone(),
+            texture_ws: group.texture_ws.clone(),
+            face_index_pairs,
+            lines: group.lines.clone(),
+            points: group.points.clone(),
+        })
+        .collect()
+}
+
+/// Calculates the angle of `i`-th corner in polygon.
+fn corner_angle(positions: &[Vec3<f32>], i: usize) -> f32 {
+    let count = positions.len();
+    let previous = positions[(i + count - 1) % count] - positions[i];
+    let next = positions[(i + 1) % count] - positions[i];
+    match (previous.try_normalized(), next.try_normalized()) {
+        (Some(p), Some(n)) => p.dot(n).clamp(-1.0, 1.0).acos(),
+        _ => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Parser, WavefrontObj};
+
+    use std::{io::Cursor, sync::Arc};
+
+    use vek::Vec3;
+
+    fn parse(source: &str) -> WavefrontObj {
+        let mut parser = Parser::new(|_, _: &()| Ok(Cursor::new(vec![])));
+        parser.parse(source.as_bytes(), ()).unwrap()
+    }
+
+    /// Two faces folded at right angle along the edge 1-2.
+    const FOLDED: &str = "\
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 1 0 -1
+vn 0 1 0
+g flat
+s off
+f 1 2 3
+g smooth
+s 1
+f 2 4 3
+f 1 2//1 3
+";
+
+    #[test]
+    fn flat_faces_get_face_normals() {
+        let obj = parse(FOLDED).generate_normals();
+        let group = &obj.objects()[0].groups()[0];
+        let (face, _, _) = group.face_index_pairs().get(0).unwrap();
+        for pair in face {
+            assert_eq!(group.normals()[pair.2.unwrap()], Vec3::unit_z());
+        }
+        assert_eq!(face[0].2, face[1].2);
+    }
+
+    #[test]
+    fn smooth_faces_share_normals_in_smoothing_group() {
+        let obj = parse(FOLDED).generate_normals();
+        let group = &obj.objects()[0].groups()[1];
+        let (first, _, _) = group.face_index_pairs().get(0).unwrap();
+        let (second, _, _) = group.face_index_pairs().get(1).unwrap();
+
+        // Vertex 2 (index 1) is shared, but the second face keeps its normal
+        assert_eq!(second[1].2, Some(0));
+        assert_eq!(first[2].2, second[2].2);
+        let shared = group.normals()[first[2].2.unwrap()];
+        let expected = (Vec3::unit_z() + Vec3::unit_x()).normalized();
+        assert!((shared - expected).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn groups_keep_sharing_normals() {
+        let obj = parse(FOLDED).generate_normals();
+        let groups = obj.objects()[0].groups();
+        assert!(Arc::ptr_eq(&groups[0].normals, &groups[1].normals));
+        assert_eq!(groups[0].normals()[0], Vec3::unit_y());
+    }
+}
@@ -1,4 +1,9 @@
-use vek::{Vec2, Vec3};
+use std::{iter::FusedIterator, sync::Arc};
+
+use vek::{Rgb, Vec2, Vec3};
+
+/// The material name for faces without material, used in `usemtl`.
+pub(crate) const NULL_MATERIAL: &str = "(null)";
 
 /// Represents an index pair in face definition.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -7,8 +12,131 @@ pub struct FaceIndexPair(pub usize, pub Option<usize>, pub Option<usize>);
 /// Represents a vertex pair in face definition.
 pub type FaceVertexPair = (Vec3<f32>, Option<Vec2<f32>>, Option<Vec3<f32>>);
 
-/// Represents face vertex indices.
-pub type FaceIndices = (Box<[FaceIndexPair]>, Option<usize>);
+/// Represents face vertex indices, material index and smoothing group.
+/// Smoothing group is `None` if smoothing is off (`s off` or `s 0`).
+pub type FaceIndices<'a> = (&'a [FaceIndexPair], Option<usize>, Option<u32>);
+
+/// Represents vertex indices of line (`l`) or points (`p`), and material index.
+pub type ElementIndices = (Box<[FaceIndexPair]>, Option<usize>);
+
+/// Represents faces of a group.
+/// Index pairs of all faces are stored in single buffer, so adding faces
+/// doesn't allocate memory for each of them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FaceList {
+    pairs: Vec<FaceIndexPair>,
+    faces: Vec<(usize, Option<usize>, Option<u32>)>,
+}
+
+impl FaceList {
+    /// The number of faces.
+    pub fn len(&self) -> usize {
+        self.faces.len()
+    }
+
+    /// Whether there are no faces.
+    pub fn is_empty(&self) -> bool {
+        self.faces.is_empty()
+    }
+
+    /// Returns `i`-th face.
+    pub fn get(&self, i: usize) -> Option<FaceIndices<'_>> {
+        let &(end, material, smoothing) = self.faces.get(i)?;
+        let start = match i {
+            0 => 0,
+            _ => self.faces[i - 1].0,
+        };
+        Some((&self.pairs[start..end], material, smoothing))
+    }
+
+    /// Iterates all faces.
+    pub fn iter(&self) -> FaceListIter<'_> {
+        FaceListIter {
+            list: self,
+            front: 0,
+            back: self.faces.len(),
+        }
+    }
+
+    /// Index pairs of all faces, concatenated in order.
+    pub fn index_pairs(&self) -> &[FaceIndexPair] {
+        &self.pairs
+    }
+
+    /// Mutable version of `index_pairs`, used for remapping indices.
+    pub(crate) fn index_pairs_mut(&mut self) -> &mut [FaceIndexPair] {
+        &mut self.pairs
+    }
+
+    /// Appends a face.
+    pub(crate) fn push(
+        &mut self,
+        face: &[FaceIndexPair],
+        material: Option<usize>,
+        smoothing: Option<u32>,
+    ) {
+        self.pairs.extend_from_slice(face);
+        self.faces.push((self.pairs.len(), material, smoothing));
+    }
+}
+
+impl<'a> IntoIterator for &'a FaceList {
+    type Item = FaceIndices<'a>;
+    type IntoIter = FaceListIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a> FromIterator<FaceIndices<'a>> for FaceList {
+    fn from_iter<T: IntoIterator<Item = FaceIndices<'a>>>(iter: T) -> Self {
+        let mut list = FaceList::default();
+        for (face, material, smoothing) in iter {
+            list.push(face, material, smoothing);
+        }
+        list
+    }
+}
+
+/// The iterator of faces in `FaceList`.
+#[derive(Debug, Clone)]
+pub struct FaceListIter<'a> {
+    list: &'a FaceList,
+    front: usize,
+    back: usize,
+}
+
+impl<'a> Iterator for FaceListIter<'a> {
+    type Item = FaceIndices<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.front == self.back {
+            return None;
+        }
+        self.front += 1;
+        self.list.get(self.front - 1)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.back - self.front;
+        (remaining, Some(remaining))
+    }
+}
+
+impl DoubleEndedIterator for FaceListIter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.front == self.back {
+            return None;
+        }
+        self.back -= 1;
+        self.list.get(self.back)
+    }
+}
+
+impl ExactSizeIterator for FaceListIter<'_> {}
+
+impl FusedIterator for FaceListIter<'_> {}
 
 /// Represents an object in OBJ file.
 #[derive(Debug, Clone)]
@@ -35,44 +163,129 @@ impl Object {
 }
 
 /// Represents a group of object.
+/// Vertex data are shared in whole file, so faces may reference vertices
+/// defined in other groups or objects.
 #[derive(Debug, Clone)]
 pub struct Group {
     pub(crate) name: Option<Box<str>>,
-    pub(crate) vertices: Box<[Vec3<f32>]>,
-    pub(crate) texture_uvs: Box<[Vec2<f32>]>,
-    pub(crate) normals: Box<[Vec3<f32>]>,
-    pub(crate) face_index_pairs: Box<[FaceIndices]>,
+    pub(crate) names: Box<[Box<str>]>,
+    pub(crate) vertices: Arc<[Vec3<f32>]>,
+    pub(crate) texture_uvs: Arc<[Vec2<f32>]>,
+    pub(crate) normals: Arc<[Vec3<f32>]>,
+    pub(crate) colors: Option<Arc<[Rgb<f32>]>>,
+    pub(crate) vertex_ws: Option<Arc<[f32]>>,
+    pub(crate) texture_ws: Option<Arc<[f32]>>,
+    pub(crate) face_index_pairs: FaceList,
+    pub(crate) lines: Box<[ElementIndices]>,
+    pub(crate) points: Box<[ElementIndices]>,
 }
 
 impl Group {
     /// The name of this group.
+    /// If the group belongs to multiple groups (`g a b`), names are joined with a space.
     pub fn name(&self) -> Option<&str> {
         self.name.as_deref()
     }
 
-    /// The vertex definitions.
+    /// The names of groups which this group belongs to.
+    pub fn names(&self) -> &[Box<str>] {
+        &self.names
+    }
+
+    /// The vertex definitions (shared in whole file).
     pub fn vertices(&self) -> &[Vec3<f32>] {
         &self.vertices
     }
 
-    /// The material UV definitions.
+    /// The material UV definitions (shared in whole file).
     pub fn texture_uvs(&self) -> &[Vec2<f32>] {
         &self.texture_uvs
     }
 
-    /// The normal definitions (normalized).
+    /// The normal definitions (shared in whole file).
+    /// They are expected to be normalized, but not guaranteed;
+    /// use `WavefrontObj::validate` and `WavefrontObj::repair` to check and fix them.
     pub fn normals(&self) -> &[Vec3<f32>] {
         &self.normals
     }
 
-    /// The slice of face index pairs.
+    /// The vertex color definitions (shared in whole file), corresponding to `vertices()`.
+    /// Returns `None` if no vertex has color. Vertices without color are white.
+    pub fn colors(&self) -> Option<&[Rgb<f32>]> {
+        self.colors.as_deref()
+    }
+
+    /// The weight components of vertices (shared in whole file), corresponding to `vertices()`.
+    /// Returns `None` if no `v` has `w`. Missing components are 1.
+    pub fn vertex_ws(&self) -> Option<&[f32]> {
+        self.vertex_ws.as_deref()
+    }
+
+    /// The third components of texture coordinates (shared in whole file),
+    /// corresponding to `texture_uvs()`.
+    /// Returns `None` if no `vt` has `w`. Missing components are 0.
+    pub fn texture_ws(&self) -> Option<&[f32]> {
+        self.texture_ws.as_deref()
+    }
+
+    /// The 3D texture coordinate of `i`-th element in `texture_uvs()`.
+    pub fn texture_uvw(&self, i: usize) -> Vec3<f32> {
+        let w = self.texture_ws.as_ref().map_or(0.0, |ws| ws[i]);
+        Vec3::from((self.texture_uvs[i], w))
+    }
+
+    /// The list of face index pairs.
     /// Each element corresponds to face, and its elements are face index pairs.
-    pub fn face_index_pairs(&self) -> &[(Box<[FaceIndexPair]>, Option<usize>)] {
+    /// Indices are 0-based and refer `vertices()`, `texture_uvs()` and `normals()`.
+    pub fn face_index_pairs(&self) -> &FaceList {
         &self.face_index_pairs
     }
 
+    /// The slice of line index pairs.
+    /// Each element corresponds to polyline (`l`), and is indexed like `face_index_pairs()`.
+    pub fn line_index_pairs(&self) -> &[ElementIndices] {
+        &self.lines
+    }
+
+    /// The slice of point index pairs.
+    /// Each element corresponds to `p` command, and is indexed like `face_index_pairs()`.
+    pub fn point_index_pairs(&self) -> &[ElementIndices] {
+        &self.points
+    }
+
+    /// Iterates all polylines in this group.
+    pub fn lines(&self) -> GroupElements<'_> {
+        GroupElements {
+            source_group: self,
+            source_elements: &self.lines,
+            current_index: 0,
+        }
+    }
+
+    /// Iterates all line segments in this group, with material index.
+    /// Polylines are split into segments, so they can be drawn as line list.
+    pub fn line_segments(&self) -> impl Iterator<Item = ([FaceVertexPair; 2], Option<usize>)> + '_ {
+        self.lines().flat_map(|(vertices, material)| {
+            let vertices: Vec<_> = vertices.collect();
+            let segments: Vec<_> = vertices
+                .windows(2)
+                .map(|w| ([w[0], w[1]], material))
+                .collect();
+            segments
+        })
+    }
+
+    /// Iterates all point sets in this group.
+    pub fn points(&self) -> GroupElements<'_> {
+        GroupElements {
+            source_group: self,
+            source_elements: &self.points,
+            current_index: 0,
+        }
+    }
+
     /// Iterates all faces in this group.
-    pub fn faces(&self) -> GroupFaces {
+    pub fn faces(&self) -> GroupFaces<'_> {
         GroupFaces {
             source_group: self,
             current_index: 0,
@@ -92,19 +305,38 @@ impl<'a> Iterator for GroupFaces<'a> {
     type Item = (FaceVertices<'a>, Option<usize>);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.current_index < self.source_group.face_index_pairs.len() {
-            let (vertices, material) = &self.source_group.face_index_pairs[self.current_index];
-
-            let result = FaceVertices {
-                source_group: self.source_group,
-                source_pairs: vertices,
-                current_index: 0,
-            };
-            self.current_index += 1;
-            Some((result, *material))
-        } else {
-            None
-        }
+        let (vertices, material, _) = self.source_group.face_index_pairs.get(self.current_index)?;
+        let result = FaceVertices {
+            source_group: self.source_group,
+            source_pairs: vertices,
+            current_index: 0,
+        };
+        self.current_index += 1;
+        Some((result, material))
+    }
+}
+
+/// The iterator adaptor for lines or points in `Group`.
+/// It returns another iterator which iterates vertices in each element.
+#[derive(Debug)]
+pub struct GroupElements<'a> {
+    source_group: &'a Group,
+    source_elements: &'a [ElementIndices],
+    current_index: usize,
+}
+
+impl<'a> Iterator for GroupElements<'a> {
+    type Item = (FaceVertices<'a>, Option<usize>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (vertices, material) = self.source_elements.get(self.current_index)?;
+        let result = FaceVertices {
+            source_group: self.source_group,
+            source_pairs: vertices,
+            current_index: 0,
+        };
+        self.current_index += 1;
+        Some((result, *material))
     }
 }
 
@@ -134,3 +366,66 @@ impl<'a> Iterator for FaceVertices<'a> {
         }
     }
 }
+
+/// Checks whether two groups refer the same vertex data.
+pub(crate) fn shares_vertex_data(lhs: &Group, rhs: &Group) -> bool {
+    Arc::ptr_eq(&lhs.vertices, &rhs.vertices)
+        && Arc::ptr_eq(&lhs.texture_uvs, &rhs.texture_uvs)
+        && Arc::ptr_eq(&lhs.normals, &rhs.normals)
+        && shares_optional(&lhs.colors, &rhs.colors)
+        && shares_optional(&lhs.vertex_ws, &rhs.vertex_ws)
+        && shares_optional(&lhs.texture_ws, &rhs.texture_ws)
+}
+
+/// Checks whether two optional data are the same.
+fn shares_optional<T>(lhs: &Option<Arc<[T]>>, rhs: &Option<Arc<[T]>>) -> bool {
+    match (lhs, rhs) {
+        (Some(l), Some(r)) => Arc::ptr_eq(l, r),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Checks whether all groups refer the same vertex data.
+/// Groups in `Object` and `WavefrontObj` always do, since vertex data are
+/// shared in whole file, and operations on them keep it.
+pub(crate) fn all_share_vertex_data<'a>(groups: impl IntoIterator<Item = &'a Group>) -> bool {
+    let mut groups = groups.into_iter();
+    match groups.next() {
+        Some(first) => groups.all(|g| shares_vertex_data(first, g)),
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn face_list_keeps_faces_in_order() {
+        let triangle = [
+            FaceIndexPair(0, None, None),
+            FaceIndexPair(1, None, None),
+            FaceIndexPair(2, None, None),
+        ];
+        let quad = [
+            FaceIndexPair(0, Some(0), None),
+            FaceIndexPair(1, Some(1), None),
+            FaceIndexPair(2, Some(2), None),
+            FaceIndexPair(3, Some(3), None),
+        ];
+        let mut faces = FaceList::default();
+        faces.push(&triangle, None, Some(1));
+        faces.push(&quad, Some(2), None);
+
+        assert_eq!(faces.len(), 2);
+        assert_eq!(faces.get(0), Some((&triangle[..], None, Some(1))));
+        assert_eq!(faces.get(1), Some((&quad[..], Some(2), None)));
+        assert_eq!(faces.get(2), None);
+        assert_eq!(faces.index_pairs().len(), 7);
+
+        let reversed: Vec<_> = faces.iter().rev().map(|(f, _, _)| f.len()).collect();
+        assert_eq!(reversed, [4, 3]);
+        assert_eq!(faces.iter().collect::<FaceList>(), faces);
+    }
+}
@@ -1,76 +1,34 @@
 use crate::{
+    command::{
+        is_continued, logical_lines, Decoded, MtlCommand, MtlCommands, ObjCommand, ObjCommands,
+        ObjDecoder,
+    },
+    diagnostic::{Diagnostic, DiagnosticKind, Diagnostics},
     mtl::{Material, MaterialProperty},
-    obj::{FaceIndexPair, Group, Object},
-    Error, Result, WavefrontObj,
+    obj::{ElementIndices, FaceIndexPair, FaceList, Group, Object, NULL_MATERIAL},
+    resolver::Resolver,
+    Location, Result, WavefrontObj,
 };
 
 use std::{
     collections::HashMap,
     io::{prelude::*, BufReader},
-    mem::take,
-    path::{Path, PathBuf},
-    str::FromStr,
+    mem::{replace, take},
+    path::Path,
+    sync::Arc,
+    thread::{available_parallelism, scope},
 };
 
 use log::warn;
-use vek::{Vec2, Vec3};
+use vek::{Rgb, Vec2, Vec3};
 
-/// Represents the abstract data of a line in OBJ file.
-#[derive(Debug, Clone, PartialEq)]
-enum ObjCommand {
-    /// `mtllib`
-    MaterialLibrary(Box<Path>),
-
-    /// `usemtl`
-    UseMaterial(Box<str>),
-
-    /// `o`
-    Object(Option<Box<str>>),
-
-    /// `g`
-    Group(Option<Box<str>>),
-
-    /// `v`
-    Vertex(Vec3<f32>),
-
-    /// `vt`
-    VertexUv(Vec2<f32>),
-
-    /// `vn`
-    VertexNormal(Vec3<f32>),
-
-    /// `f`
-    Face(Box<[FaceIndexPair]>),
-
-    /// Any other unknown keyword
-    Unknown(Box<str>, Box<[Box<str>]>),
-}
-
-/// Represents the abstract data of a line in MTL file.
-#[derive(Debug, Clone, PartialEq)]
-enum MtlCommand {
-    /// `newmtl`
-    NewMaterial(Box<str>),
-
-    /// Integer property
-    Integer(Box<str>, u32),
-
-    /// Float property
-    Float(Box<str>, f32),
-
-    /// Vector property
-    Vector(Box<str>, Vec3<f32>),
-
-    /// Path property
-    Path(Box<str>, Box<Path>),
-
-    /// Any other unknown keyword
-    Unknown(Box<str>, Box<[Box<str>]>),
-}
+/// Resolver function for `mtllib` command.
+type IncludeFunction<C, R> = Box<dyn FnMut(&Path, &C) -> Result<R>>;
 
 /// Represents the parser of OBJ/MTL.
 pub struct Parser<C, R> {
-    include_function: Box<dyn FnMut(&Path, &C) -> Result<R>>,
+    include_function: IncludeFunction<C, R>,
+    strict: bool,
 }
 
 impl<C, R: Read> Parser<C, R> {
@@ -84,414 +42,748 @@ impl<C, R: Read> Parser<C, R> {
     pub fn new(include_function: impl FnMut(&Path, &C) -> Result<R> + 'static) -> Parser<C, R> {
         Parser {
             include_function: Box::new(include_function),
+            strict: false,
         }
     }
 
+    /// Creates an instance of `Parser` resolving MTL files with `resolver`.
+    /// The context object is not used for resolving.
+    pub fn with_resolver(resolver: impl Resolver<Reader = R> + 'static) -> Parser<C, R> {
+        Parser::new(move |path, _: &C| resolver.resolve(path))
+    }
+
+    /// Sets whether to treat diagnostics as errors.
+    /// In strict mode, problems such as unknown keywords fail parsing
+    /// with `Error::Diagnostic` instead of being collected in `WavefrontObj::diagnostics`.
+    pub fn strict(mut self, strict: bool) -> Parser<C, R> {
+        self.strict = strict;
+        self
+    }
+
     /// Parses the OBJ file.
     pub fn parse(&mut self, reader: impl Read, context: C) -> Result<WavefrontObj> {
-        let mut reader = BufReader::new(reader);
-
-        let mut line_buffer = String::with_capacity(1024);
-        self.parse_impl(context, move || {
-            loop {
-                line_buffer.clear();
-                let read_size = reader.read_line(&mut line_buffer)?;
-                if read_size == 0 {
-                    return Ok(None);
+        let mut commands = ObjCommands::new(BufReader::new(reader));
+        let mut builder = ObjBuilder::new(self.strict);
+        while let Some(decoded) = commands.next_decoded() {
+            match decoded? {
+                Decoded::Face => builder.push_face(commands.face()),
+                Decoded::Command(ObjCommand::MaterialLibrary(paths)) => {
+                    self.include(&paths, &context, &commands.location(), &mut builder)?;
                 }
-
-                let trimmed = line_buffer.trim();
-                if trimmed.is_empty() || trimmed.starts_with('#') {
-                    continue;
-                }
-                break;
+                Decoded::Command(command) => builder.push(command, || commands.location())?,
             }
+        }
 
-            let mut elements = line_buffer.trim().split_whitespace();
-            let keyword = elements
-                .next()
-                .expect("Each line should have at least one element");
-            let data: Vec<&str> = elements.collect();
-            let command = parse_obj_line(keyword, &data)?;
-
-            Ok(Some(command))
-        })
+        Ok(builder.finish())
     }
 
-    #[allow(unused_assignments)]
-    fn parse_impl(
-        &mut self,
-        context: C,
-        mut fetch_line: impl FnMut() -> Result<Option<ObjCommand>>,
-    ) -> Result<WavefrontObj> {
-        let mut materials = Default::default();
-        let mut current_material = None;
-        let mut objects = vec![];
-        let mut object_name: Option<Box<str>> = Default::default();
-        let mut groups = vec![];
-        let mut group_name: Option<Box<str>> = Default::default();
-        let mut vertices = vec![];
-        let mut uvs = vec![];
-        let mut normals = vec![];
-        let mut faces = vec![];
-        let mut vo = 0;
-        let mut to = 0;
-        let mut no = 0;
-
-        macro_rules! commit_group {
-            ($n: expr) => {
-                vo += vertices.len();
-                to += uvs.len();
-                no += normals.len();
-                let committing_name = group_name.take();
-                group_name = $n;
-                let group = Group {
-                    name: committing_name,
-                    vertices: take(&mut vertices).into_boxed_slice(),
-                    texture_uvs: take(&mut uvs).into_boxed_slice(),
-                    normals: take(&mut normals).into_boxed_slice(),
-                    face_index_pairs: take(&mut faces).into_boxed_slice(),
-                };
+    /// Parses the OBJ source in memory with multiple threads.
+    /// The source is split into chunks at line boundaries and each chunk is
+    /// decoded in parallel, then commands are processed in the file order.
+    /// The result is identical to `parse`.
+    pub fn parse_parallel(&mut self, source: &str, context: C) -> Result<WavefrontObj> {
+        let threads = available_parallelism().map_or(1, |n| n.get());
+        self.parse_chunks(split_chunks(source, threads), context)
+    }
 
-                if group.face_index_pairs.len() > 0 {
-                    groups.push(group);
-                }
-            };
+    /// Decodes the chunks in parallel and processes them in order.
+    fn parse_chunks(&mut self, chunks: Vec<&str>, context: C) -> Result<WavefrontObj> {
+        // Each chunk starts with the state after preceding chunks
+        let counts: Vec<_> = scope(|s| {
+            let handles: Vec<_> = chunks
+                .iter()
+                .map(|chunk| {
+                    s.spawn(move || {
+                        let mut decoder = ObjDecoder::default();
+                        decoder.skip(chunk);
+                        decoder
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().expect("Counting thread should not panic"))
+                .collect()
+        });
+        let mut decoders = vec![];
+        let mut state = ObjDecoder::default();
+        for count in &counts {
+            decoders.push(state.clone());
+            state.advance(count);
         }
 
-        macro_rules! commit_object {
-            ($n: expr) => {
-                let committed_name = object_name.take();
-                object_name = $n;
-                let object = Object {
-                    name: committed_name,
-                    groups: take(&mut groups).into_boxed_slice(),
+        let decoded: Vec<_> = scope(|s| {
+            let handles: Vec<_> = chunks
+                .iter()
+                .zip(decoders)
+                .map(|(chunk, decoder)| s.spawn(move || decode_chunk(chunk, decoder)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().expect("Decoding thread should not panic"))
+                .collect()
+        });
+
+        let mut builder = ObjBuilder::new(self.strict);
+        for chunk in decoded {
+            let chunk = chunk?;
+            let mut faces = chunk.faces.iter();
+            let mut locations = chunk.locations.into_iter();
+            for decoded in chunk.commands {
+                let command = match decoded {
+                    Decoded::Face => {
+                        let (face, _, _) = faces.next().expect("Face should be recorded");
+                        builder.push_face(face);
+                        continue;
+                    }
+                    Decoded::Command(command) => command,
                 };
-                if object.groups.len() > 0 {
-                    objects.push(object);
+                let location = if needs_location(&command) {
+                    locations.next()
+                } else {
+                    None
+                };
+                let location = || location.expect("Location should be recorded");
+                match command {
+                    ObjCommand::MaterialLibrary(paths) => {
+                        self.include(&paths, &context, &location(), &mut builder)?;
+                    }
+                    command => builder.push(command, location)?,
                 }
-            };
+            }
         }
 
-        while let Some(command) = fetch_line()? {
-            match command {
-                // mtllib
-                ObjCommand::MaterialLibrary(path) => {
-                    let mtl_reader = (self.include_function)(&path, &context)?;
-                    materials = self.parse_mtl(mtl_reader)?;
-                }
+        Ok(builder.finish())
+    }
 
-                // o
-                ObjCommand::Object(name) => {
-                    commit_group!(None);
-                    commit_object!(name);
-                }
+    /// Resolves and parses MTL files in `mtllib`.
+    /// Materials are added to the ones loaded so far.
+    fn include(
+        &mut self,
+        paths: &[Box<Path>],
+        context: &C,
+        location: &Location,
+        builder: &mut ObjBuilder,
+    ) -> Result<()> {
+        for path in paths {
+            let mtl_reader =
+                (self.include_function)(path, context).map_err(|e| e.at(location.clone()))?;
+            let (materials, diagnostics) = self.parse_mtl(mtl_reader, path)?;
+            builder.add_materials(materials, diagnostics);
+        }
+        Ok(())
+    }
 
-                //g
-                ObjCommand::Group(name) => {
-                    commit_group!(name);
-                }
+    /// Parses MTL file.
+    /// The reader will be wrapped with `BufReader`, so you don't have to
+    /// do so.
+    fn parse_mtl(
+        &self,
+        reader: impl Read,
+        path: &Path,
+    ) -> Result<(Box<[Material]>, Vec<Diagnostic>)> {
+        let mut commands = MtlCommands::new(BufReader::new(reader), path);
+        let mut builder = MtlBuilder::new(self.strict);
+        while let Some(command) = commands.next() {
+            builder.push(command?, || commands.location())?;
+        }
 
-                // v
-                ObjCommand::Vertex(vertex) => {
-                    vertices.push(vertex);
-                }
+        Ok(builder.finish())
+    }
+}
 
-                // vt
-                ObjCommand::VertexUv(uv) => {
-                    uvs.push(uv);
-                }
+/// The minimum length of chunks for `Parser::parse_parallel`.
+const MIN_CHUNK_LENGTH: usize = 64 * 1024;
+
+/// Splits the source into at most `count` chunks at line boundaries.
+/// Lines continued with `\\` are kept in the same chunk.
+fn split_chunks(source: &str, count: usize) -> Vec<&str> {
+    let count = count.clamp(1, source.len() / MIN_CHUNK_LENGTH + 1);
+    let mut chunks = vec![];
+    let mut rest = source;
+    for i in (1..count).rev() {
+        let mut end = rest.len() / (i + 1);
+        loop {
+            end = match rest.as_bytes()[end..].iter().position(|&b| b == b'\n') {
+                Some(p) => end + p + 1,
+                None => rest.len(),
+            };
+            let start = rest[..end - 1].rfind('\n').map_or(0, |p| p + 1);
+            if end == rest.len() || !is_continued(&rest[start..end]) {
+                break;
+            }
+        }
+        if end == rest.len() {
+            break;
+        }
+        let (chunk, next) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = next;
+    }
+    chunks.push(rest);
+    chunks
+}
 
-                // vn
-                ObjCommand::VertexNormal(normal) => {
-                    normals.push(normal);
-                }
+/// Commands decoded from a chunk.
+#[derive(Debug, Default)]
+struct DecodedChunk {
+    commands: Vec<Decoded>,
 
-                // f
-                ObjCommand::Face(face) => {
-                    // TODO: チェックする
-                    let mut adjusted_face = vec![];
-                    for FaceIndexPair(raw_v, raw_t, raw_n) in face.into_vec() {
-                        let adjusted_v = raw_v - vo;
-                        let adjusted_t = raw_t.map(|i| i - to);
-                        let adjusted_n = raw_n.map(|i| i - no);
-                        adjusted_face.push(FaceIndexPair(adjusted_v, adjusted_t, adjusted_n))
-                    }
-                    faces.push((adjusted_face.into_boxed_slice(), current_material));
-                }
+    /// Faces corresponding to `Decoded::Face` in `commands`, in order.
+    faces: FaceList,
 
-                // usemtl
-                ObjCommand::UseMaterial(material_name) => {
-                    current_material = materials
-                        .iter()
-                        .position(|m| m.name() == &material_name[..]);
-                }
+    /// Locations of commands which may need them (see `needs_location`), in order.
+    locations: Vec<Location>,
+}
 
-                // unknown
-                ObjCommand::Unknown(k, _) => {
-                    warn!("Unprocessable command: {:?}", k);
+/// Decodes all lines in the chunk.
+fn decode_chunk(chunk: &str, mut decoder: ObjDecoder) -> Result<DecodedChunk> {
+    let mut decoded = DecodedChunk::default();
+    for line in logical_lines(chunk) {
+        match decoder.decode_line(line)? {
+            Some(Decoded::Face) => decoded.faces.push(decoder.face(), None, None),
+            Some(Decoded::Command(command)) => {
+                if needs_location(&command) {
+                    decoded.locations.push(decoder.location(line));
                 }
+                decoded.commands.push(Decoded::Command(command));
+                continue;
             }
+            None => continue,
         }
+        decoded.commands.push(Decoded::Face);
+    }
+    Ok(decoded)
+}
 
-        commit_group!(None);
-        commit_object!(None);
+/// Whether the command may cause errors or diagnostics after decoding.
+fn needs_location(command: &ObjCommand) -> bool {
+    matches!(
+        command,
+        ObjCommand::MaterialLibrary(_) | ObjCommand::UseMaterial(_) | ObjCommand::Unknown(..)
+    )
+}
 
-        Ok(WavefrontObj {
-            materials,
-            objects: objects.into_boxed_slice(),
-        })
+/// Builds `WavefrontObj` from OBJ commands.
+#[derive(Debug, Default)]
+pub(crate) struct ObjBuilder {
+    materials: Vec<Material>,
+    current_material: Option<usize>,
+    current_smoothing: Option<u32>,
+    objects: Vec<Object>,
+    object_name: Option<Box<str>>,
+    groups: Vec<Group>,
+    group_names: Box<[Box<str>]>,
+    vertices: Vec<Vec3<f32>>,
+    uvs: Vec<Vec2<f32>>,
+    normals: Vec<Vec3<f32>>,
+    colors: Vec<Rgb<f32>>,
+    has_colors: bool,
+    vertex_ws: Vec<f32>,
+    has_vertex_ws: bool,
+    texture_ws: Vec<f32>,
+    has_texture_ws: bool,
+    faces: FaceList,
+    lines: Vec<ElementIndices>,
+    points: Vec<ElementIndices>,
+    parameter_vertices: Vec<Vec3<f32>>,
+    diagnostics: Diagnostics,
+}
+
+impl ObjBuilder {
+    pub(crate) fn new(strict: bool) -> ObjBuilder {
+        ObjBuilder {
+            diagnostics: Diagnostics::new(strict),
+            ..Default::default()
+        }
     }
 
-    /// Parses MTL file.
-    /// The reader will be wrapped with `BufReader`, so you don't have to
-    /// do so.
-    fn parse_mtl(&self, reader: impl Read) -> Result<Box<[Material]>> {
-        let mut materials = vec![];
-        let mut properties = HashMap::new();
-        let mut name = String::new().into_boxed_str();
+    /// Adds the materials loaded with `mtllib`, and diagnostics in loading them.
+    pub(crate) fn add_materials(
+        &mut self,
+        materials: Box<[Material]>,
+        diagnostics: Vec<Diagnostic>,
+    ) {
+        self.materials.extend(materials.into_vec());
+        self.diagnostics.extend(diagnostics);
+    }
 
-        let mut reader = BufReader::new(reader);
-        let mut line_buffer = String::with_capacity(1024);
-        loop {
-            line_buffer.clear();
-            let read_size = reader.read_line(&mut line_buffer)?;
-            if read_size == 0 {
-                break;
+    /// Processes a command.
+    /// `location` is called only when the command has problems.
+    /// `mtllib` should be handled by the caller with `add_materials`.
+    pub(crate) fn push(
+        &mut self,
+        command: ObjCommand,
+        location: impl FnOnce() -> Location,
+    ) -> Result<()> {
+        match command {
+            // o
+            ObjCommand::Object(name) => {
+                self.commit_group(Box::default());
+                self.commit_object(name);
             }
 
-            let trimmed = line_buffer.trim();
-            if trimmed.is_empty() || trimmed.starts_with('#') {
-                continue;
+            // g
+            ObjCommand::Group(names) => {
+                self.commit_group(names);
             }
 
-            let mut elements = line_buffer.trim().split_whitespace();
-            let keyword = elements
-                .next()
-                .expect("Each line should have at least one element");
-            let data: Vec<&str> = elements.collect();
-
-            let command = parse_mtl_line(keyword, &data)?;
-            match command {
-                MtlCommand::NewMaterial(next_name) => {
-                    if !properties.is_empty() {
-                        let material = Material { name, properties };
-                        materials.push(material);
-                    }
+            // v
+            ObjCommand::Vertex(vertex, w, color) => {
+                self.vertices.push(vertex);
+                self.has_vertex_ws |= w.is_some();
+                self.vertex_ws.push(w.unwrap_or(1.0));
+                self.has_colors |= color.is_some();
+                self.colors.push(color.unwrap_or_else(Rgb::white));
+            }
 
-                    properties = HashMap::new();
-                    name = next_name;
-                }
-                MtlCommand::Vector(n, v) => {
-                    properties.insert(n.into(), MaterialProperty::Vector(v));
-                }
-                MtlCommand::Float(n, v) => {
-                    properties.insert(n.into(), MaterialProperty::Float(v));
-                }
-                MtlCommand::Integer(n, v) => {
-                    properties.insert(n.into(), MaterialProperty::Integer(v));
-                }
-                MtlCommand::Path(n, v) => {
-                    properties.insert(n.into(), MaterialProperty::Path(v));
-                }
-                MtlCommand::Unknown(keyword, _) => {
-                    warn!("Unsupported MTL keyword: {}", keyword);
+            // vt
+            ObjCommand::VertexUv(uv, w) => {
+                self.uvs.push(uv);
+                self.has_texture_ws |= w.is_some();
+                self.texture_ws.push(w.unwrap_or_default());
+            }
+
+            // vn
+            ObjCommand::VertexNormal(normal) => {
+                self.normals.push(normal);
+            }
+
+            // vp (free-form geometry is not supported, kept only)
+            ObjCommand::ParameterVertex(vertex) => {
+                self.parameter_vertices.push(vertex);
+            }
+
+            // f
+            ObjCommand::Face(face) => self.push_face(&face),
+
+            // l
+            ObjCommand::Line(line) => {
+                self.lines.push((line, self.current_material));
+            }
+
+            // p
+            ObjCommand::Points(points) => {
+                self.points.push((points, self.current_material));
+            }
+
+            // s
+            ObjCommand::SmoothingGroup(group) => {
+                self.current_smoothing = group;
+            }
+
+            // usemtl
+            ObjCommand::UseMaterial(material_name) => {
+                self.current_material = self
+                    .materials
+                    .iter()
+                    .position(|m| m.name() == &material_name[..]);
+                if self.current_material.is_none() && &material_name[..] != NULL_MATERIAL {
+                    let kind = DiagnosticKind::UnknownMaterial(material_name);
+                    self.diagnostics.report(kind, location())?;
                 }
             }
+
+            // mtllib
+            ObjCommand::MaterialLibrary(paths) => {
+                warn!("Unresolved material library: {:?}", paths);
+            }
+
+            // unknown
+            ObjCommand::Unknown(keyword, _) => {
+                let kind = DiagnosticKind::UnknownKeyword(keyword);
+                self.diagnostics.report(kind, location())?;
+            }
         }
 
-        let last_material = Material { name, properties };
-        materials.push(last_material);
+        Ok(())
+    }
 
-        Ok(materials.into_boxed_slice())
+    /// Processes a face, which is decoded without `ObjCommand`.
+    pub(crate) fn push_face(&mut self, face: &[FaceIndexPair]) {
+        self.faces
+            .push(face, self.current_material, self.current_smoothing);
     }
-}
 
-/// Parses a line of OBJ file.
-fn parse_obj_line(keyword: &str, data: &[&str]) -> Result<ObjCommand> {
-    let value = match keyword {
-        "mtllib" => {
-            let value = data.get(0).unwrap_or(&"").replace("\\\\", "\\");
-            let filename = PathBuf::from_str(&value).map_err(|_| Error::PathNotFound(value))?;
-            ObjCommand::MaterialLibrary(filename.into_boxed_path())
+    /// Finishes building.
+    pub(crate) fn finish(mut self) -> WavefrontObj {
+        self.commit_group(Box::default());
+        self.commit_object(None);
+
+        // Shared pools are filled after whole file is read
+        let vertices: Arc<[_]> = take(&mut self.vertices).into();
+        let uvs: Arc<[_]> = take(&mut self.uvs).into();
+        let normals: Arc<[_]> = take(&mut self.normals).into();
+        let colors: Option<Arc<[_]>> = self.has_colors.then(|| take(&mut self.colors).into());
+        let vertex_ws: Option<Arc<[_]>> =
+            self.has_vertex_ws.then(|| take(&mut self.vertex_ws).into());
+        let texture_ws: Option<Arc<[_]>> = self
+            .has_texture_ws
+            .then(|| take(&mut self.texture_ws).into());
+        for group in self.objects.iter_mut().flat_map(|o| o.groups.iter_mut()) {
+            group.vertices = vertices.clone();
+            group.texture_uvs = uvs.clone();
+            group.normals = normals.clone();
+            group.colors = colors.clone();
+            group.vertex_ws = vertex_ws.clone();
+            group.texture_ws = texture_ws.clone();
         }
-        "usemtl" => {
-            let material = data.get(0).ok_or(Error::NotEnoughData {
-                expected: 1,
-                found: 0,
-            })?;
-            ObjCommand::UseMaterial(material.to_string().into_boxed_str())
-        }
-        "o" => {
-            let name = data.get(0).map(|name| name.to_string().into_boxed_str());
-            ObjCommand::Object(name)
-        }
-        "g" => {
-            let name = data.get(0).map(|name| name.to_string().into_boxed_str());
-            ObjCommand::Group(name)
-        }
-        "v" => {
-            let value = take_vec3(data)?;
-            ObjCommand::Vertex(value)
-        }
-        "vt" => {
-            let value = take_vec2(data)?;
-            ObjCommand::VertexUv(value)
-        }
-        "vn" => {
-            let value = take_vec3(data)?;
-            ObjCommand::VertexNormal(value)
+
+        WavefrontObj {
+            materials: self.materials.into_boxed_slice(),
+            parameter_vertices: self.parameter_vertices.into_boxed_slice(),
+            objects: self.objects.into_boxed_slice(),
+            diagnostics: self.diagnostics.finish().into_boxed_slice(),
         }
-        "f" => {
-            let face = parse_face(data)?;
-            ObjCommand::Face(face)
+    }
+
+    fn commit_group(&mut self, next_names: Box<[Box<str>]>) {
+        let committing_names = replace(&mut self.group_names, next_names);
+        let group = Group {
+            name: (!committing_names.is_empty()).then(|| committing_names.join(" ").into()),
+            names: committing_names,
+            vertices: Default::default(),
+            texture_uvs: Default::default(),
+            normals: Default::default(),
+            colors: None,
+            vertex_ws: None,
+            texture_ws: None,
+            face_index_pairs: take(&mut self.faces),
+            lines: take(&mut self.lines).into_boxed_slice(),
+            points: take(&mut self.points).into_boxed_slice(),
+        };
+
+        if !group.face_index_pairs.is_empty() || !group.lines.is_empty() || !group.points.is_empty()
+        {
+            self.groups.push(group);
         }
-        _ => {
-            let owned_data: Vec<_> = data
-                .iter()
-                .map(|s| s.to_string().into_boxed_str())
-                .collect();
-            ObjCommand::Unknown(keyword.into(), owned_data.into_boxed_slice())
+    }
+
+    fn commit_object(&mut self, next_name: Option<Box<str>>) {
+        let committing_name = replace(&mut self.object_name, next_name);
+        let object = Object {
+            name: committing_name,
+            groups: take(&mut self.groups).into_boxed_slice(),
+        };
+
+        if !object.groups.is_empty() {
+            self.objects.push(object);
         }
-    };
+    }
+}
 
-    Ok(value)
+/// Builds materials from MTL commands.
+#[derive(Debug, Default)]
+pub(crate) struct MtlBuilder {
+    materials: Vec<Material>,
+    properties: HashMap<String, MaterialProperty>,
+    name: Option<Box<str>>,
+    diagnostics: Diagnostics,
 }
 
-/// Parses a line of MTL file.
-fn parse_mtl_line(keyword: &str, data: &[&str]) -> Result<MtlCommand> {
-    let value = match keyword {
-        "newmtl" => {
-            let name = data.get(0).unwrap_or(&"").to_string();
-            MtlCommand::NewMaterial(name.into_boxed_str())
-        }
-        "illum" => {
-            let value = take_single(data)?;
-            MtlCommand::Integer(keyword.into(), value)
+impl MtlBuilder {
+    pub(crate) fn new(strict: bool) -> MtlBuilder {
+        MtlBuilder {
+            diagnostics: Diagnostics::new(strict),
+            ..Default::default()
         }
-        k if k.starts_with('K') => {
-            let value = take_vec3(data)?;
-            MtlCommand::Vector(keyword.into(), value)
+    }
+
+    /// Processes a command.
+    /// `location` is called only when the command has problems.
+    pub(crate) fn push(
+        &mut self,
+        command: MtlCommand,
+        location: impl FnOnce() -> Location,
+    ) -> Result<()> {
+        let properties = &mut self.properties;
+        match command {
+            MtlCommand::NewMaterial(next_name) => {
+                self.commit_material(Some(next_name));
+            }
+            MtlCommand::Vector(n, v) => {
+                properties.insert(n.into(), MaterialProperty::Vector(v));
+            }
+            MtlCommand::Spectral(n, path, factor) => {
+                properties.insert(n.into(), MaterialProperty::Spectral(path, factor));
+            }
+            MtlCommand::Xyz(n, v) => {
+                properties.insert(n.into(), MaterialProperty::Xyz(v));
+            }
+            MtlCommand::Float(n, v) => {
+                properties.insert(n.into(), MaterialProperty::Float(v));
+            }
+            MtlCommand::Dissolve(v, halo) => {
+                properties.insert("d".into(), MaterialProperty::Dissolve(v, halo));
+            }
+            MtlCommand::Integer(n, v) => {
+                properties.insert(n.into(), MaterialProperty::Integer(v));
+            }
+            MtlCommand::Texture(n, v, unknown_options) => {
+                properties.insert(n.into(), MaterialProperty::Texture(v));
+                if !unknown_options.is_empty() {
+                    let location = location();
+                    for option in unknown_options.into_vec() {
+                        let kind = DiagnosticKind::UnknownTextureOption(option);
+                        self.diagnostics.report(kind, location.clone())?;
+                    }
+                }
+            }
+            MtlCommand::Unknown(keyword, _) => {
+                let kind = DiagnosticKind::UnknownKeyword(keyword);
+                self.diagnostics.report(kind, location())?;
+            }
         }
-        k if k.starts_with('N') => {
-            let value = take_single(data)?;
-            MtlCommand::Float(keyword.into(), value)
+
+        Ok(())
+    }
+
+    /// Finishes building.
+    /// Returns materials and diagnostics.
+    pub(crate) fn finish(mut self) -> (Box<[Material]>, Vec<Diagnostic>) {
+        self.commit_material(None);
+        (self.materials.into_boxed_slice(), self.diagnostics.finish())
+    }
+
+    fn commit_material(&mut self, next_name: Option<Box<str>>) {
+        let name = replace(&mut self.name, next_name);
+        let properties = take(&mut self.properties);
+        if name.is_some() || !properties.is_empty() {
+            let material = Material {
+                name: name.unwrap_or_default(),
+                properties,
+            };
+            self.materials.push(material);
         }
-        k if k.starts_with("map_") => {
-            let value = data.get(0).unwrap_or(&"").replace("\\\\", "\\");
-            let value = PathBuf::from_str(&value).map_err(|_| Error::PathNotFound(value))?;
-            MtlCommand::Path(keyword.into(), value.into_boxed_path())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Error, FaceIndexPair};
+
+    use std::{fmt::Write, io::Cursor};
+
+    fn parser() -> Parser<(), Cursor<&'static [u8]>> {
+        Parser::new(|_, _| {
+            Ok(Cursor::new(
+                b"newmtl red\nKd 1 0 0\nnewmtl blue\nKd 0 0 1\n".as_slice(),
+            ))
+        })
+    }
+
+    /// Makes a source with many faces, continued lines and problems to be diagnosed.
+    fn source() -> String {
+        let mut source = String::from("mtllib a.mtl\no strip\n");
+        for i in 0..5000 {
+            writeln!(source, "v {} 0 0\nv {} 1 0 \\\n  1 0.5 0.5", i, i).unwrap();
+            if i > 0 {
+                let material = ["red", "blue", "green"][i % 3];
+                writeln!(source, "usemtl {}\ns {}", material, i % 4).unwrap();
+                writeln!(source, "f -4 -3 -1 -2\nunknown {}", i).unwrap();
+            }
         }
-        _ => {
-            let owned_data: Vec<_> = data
-                .iter()
-                .map(|s| s.to_string().into_boxed_str())
-                .collect();
-            MtlCommand::Unknown(keyword.into(), owned_data.into_boxed_slice())
+        source
+    }
+
+    #[test]
+    fn parallel_parsing_is_same_as_sequential() {
+        let source = source();
+        let sequential = parser().parse(source.as_bytes(), ()).unwrap();
+        assert_eq!(sequential.objects()[0].groups()[0].faces().count(), 4999);
+        assert!(!sequential.diagnostics().is_empty());
+
+        for count in [1, 2, 7] {
+            let chunks = split_chunks(&source, count);
+            assert_eq!(chunks.concat(), source);
+            let parallel = parser().parse_chunks(chunks, ()).unwrap();
+            assert_eq!(format!("{:?}", parallel), format!("{:?}", sequential));
         }
-    };
+    }
 
-    Ok(value)
-}
+    #[test]
+    fn errors_in_chunks_have_line_numbers_in_file() {
+        let mut source = source();
+        source += "f 1 2 100000\n";
+        let line = source.lines().count();
+        let sequential = parser().parse(source.as_bytes(), ()).unwrap_err();
+        let parallel = parser()
+            .parse_chunks(split_chunks(&source, 7), ())
+            .unwrap_err();
+        assert_eq!(sequential.location().unwrap().line(), line);
+        assert_eq!(parallel.to_string(), sequential.to_string());
+    }
 
-/// Parses a `f` command.
-fn parse_face(vertices: impl IntoIterator<Item = impl AsRef<str>>) -> Result<Box<[FaceIndexPair]>> {
-    let not_enough = |c| Error::NotEnoughData {
-        expected: 3,
-        found: c,
-    };
-
-    let mut index_pairs = vec![];
-    for vertex in vertices {
-        let indices_str = vertex.as_ref().split('/');
-        let mut indices = indices_str.map(|s| {
-            if !s.is_empty() {
-                Some(s.parse::<usize>())
-            } else {
-                None
-            }
-        });
-        let vertex_index = match indices.next() {
-            Some(Some(Ok(v))) => v - 1,
-            Some(Some(Err(_))) => return Err(Error::ParseError),
-            Some(None) => return Err(Error::InvalidFaceVertex),
-            None => return Err(not_enough(0)),
-        };
-        let uv_index = match indices.next() {
-            Some(Some(Ok(v))) => Some(v - 1),
-            Some(Some(Err(_))) => return Err(Error::ParseError),
-            Some(None) => None,
-            None => None,
-        };
-        let normal_index = match indices.next() {
-            Some(Some(Ok(v))) => Some(v - 1),
-            Some(Some(Err(_))) => return Err(Error::ParseError),
-            Some(None) => None,
-            None => None,
+    #[test]
+    fn blank_line_ends_continuation() {
+        let source = "v 0 0 0 \\\n\nv 1 0 0\n# v 2 0 0 \\\nv 3 0 0\nf 1 2 3\n";
+        let sequential = parser().parse(source.as_bytes(), ()).unwrap();
+        let parallel = parser().parse_parallel(source, ()).unwrap();
+        assert_eq!(format!("{:?}", parallel), format!("{:?}", sequential));
+
+        let vertices = sequential.objects()[0].groups()[0].vertices();
+        assert_eq!(
+            vertices,
+            [Vec3::zero(), Vec3::unit_x(), Vec3::new(3.0, 0.0, 0.0)]
+        );
+    }
+
+    #[test]
+    fn vertex_ws_and_parameter_vertices_are_kept() {
+        let source = "vp 0.5\nvp 0.5 0.25 2\nv 0 0 0 2\nv 1 0 0\nv 0 1 0 1 0 0 0.5\nf 1 2 3\n";
+        let obj = parser().parse(source.as_bytes(), ()).unwrap();
+        let group = &obj.objects()[0].groups()[0];
+        assert_eq!(group.vertex_ws(), Some(&[2.0, 1.0, 1.0][..]));
+        assert_eq!(group.colors().unwrap()[2], Rgb::new(0.0, 0.0, 0.5));
+        assert_eq!(
+            obj.parameter_vertices(),
+            [Vec3::new(0.5, 0.0, 1.0), Vec3::new(0.5, 0.25, 2.0)]
+        );
+        assert!(obj.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn unknown_texture_options_are_diagnosed() {
+        let mtl = "newmtl a\nmap_Kd -colorspace sRGB -foo a.png\n";
+        let parse = |strict| {
+            let mut parser = Parser::new(move |_, _: &()| Ok(mtl.as_bytes())).strict(strict);
+            parser.parse("mtllib a.mtl\n".as_bytes(), ())
         };
-        index_pairs.push(FaceIndexPair(vertex_index, uv_index, normal_index));
+
+        let obj = parse(false).unwrap();
+        let texture_map = obj.materials()[0].diffuse_map().unwrap();
+        assert_eq!(texture_map.path(), Path::new("a.png"));
+        let kinds: Vec<_> = obj.diagnostics().iter().map(|d| d.kind().clone()).collect();
+        assert_eq!(
+            kinds,
+            [
+                DiagnosticKind::UnknownTextureOption("-colorspace".into()),
+                DiagnosticKind::UnknownTextureOption("-foo".into()),
+            ]
+        );
+        assert_eq!(obj.diagnostics()[0].location().line(), 2);
+        assert!(parse(true).is_err());
     }
 
-    Ok(index_pairs.into_boxed_slice())
-}
+    #[test]
+    fn faces_reference_vertices_of_earlier_groups() {
+        let source = "o a\nv 0 0 0\nv 1 0 0\nvt 0 1\ng first\nv 0 1 0\nf 1 2 3\n\
+                      o b\nvn 0 0 1\nf 3/1/1 2/1/1 -3/1/-1\n";
+        let obj = parser().parse(source.as_bytes(), ()).unwrap();
+        let first = &obj.objects()[0].groups()[0];
+        let second = &obj.objects()[1].groups()[0];
+        assert!(Arc::ptr_eq(&first.vertices, &second.vertices));
+
+        let (face, _) = second.faces().next().unwrap();
+        let face: Vec<_> = face.collect();
+        assert_eq!(face[0].0, Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(face[2].0, Vec3::zero());
+        assert_eq!(face[1].1, Some(Vec2::new(0.0, 1.0)));
+        assert_eq!(face[1].2, Some(Vec3::unit_z()));
+    }
 
-/// Consumes the iterator and parses the first element.
-pub(crate) fn take_single<T: FromStr>(it: impl IntoIterator<Item = impl AsRef<str>>) -> Result<T> {
-    let mut it = it.into_iter();
-    let first = it.next().ok_or(Error::NotEnoughData {
-        found: 0,
-        expected: 1,
-    })?;
+    #[test]
+    fn negative_indices_are_relative_to_data_so_far() {
+        let source = "v 0 0 0\nv 1 0 0\nvt 0 0\nvn 0 0 1\nv 0 1 0\nvt 1 0\n\
+                      f -3/-2/-1 -2/-1/-1 -1/-2/1\n";
+        let obj = parser().parse(source.as_bytes(), ()).unwrap();
+        let (face, _) = obj.objects()[0].groups()[0].faces().next().unwrap();
+        let expected = [
+            (Vec3::zero(), Some(Vec2::zero()), Some(Vec3::unit_z())),
+            (Vec3::unit_x(), Some(Vec2::unit_x()), Some(Vec3::unit_z())),
+            (Vec3::unit_y(), Some(Vec2::zero()), Some(Vec3::unit_z())),
+        ];
+        assert_eq!(face.collect::<Vec<_>>(), expected);
+    }
 
-    let value = first.as_ref().parse().map_err(|_| Error::ParseError)?;
-    Ok(value)
-}
+    #[test]
+    fn zero_indices_are_invalid() {
+        for face in ["f 0 1 2", "f 1/0 2/1 3/1", "f 1//0 2 3"] {
+            let source = format!("v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\n{}\n", face);
+            match parser().parse(source.as_bytes(), ()).unwrap_err() {
+                Error::Located { location, error } => {
+                    assert_eq!(location.text(), face);
+                    assert!(matches!(*error, Error::InvalidIndex));
+                }
+                other => panic!("{:?}", other),
+            }
+        }
+    }
 
-/// Consumes the iterator and parses into `Vec2`.
-pub(crate) fn take_vec2(it: impl IntoIterator<Item = impl AsRef<str>>) -> Result<Vec2<f32>> {
-    let mut it = it.into_iter();
-    let first = it
-        .next()
-        .ok_or(Error::NotEnoughData {
-            found: 0,
-            expected: 2,
-        })
-        .and_then(|s| s.as_ref().parse().map_err(|_| Error::ParseError))?;
-    let second = it
-        .next()
-        .ok_or(Error::NotEnoughData {
-            found: 1,
-            expected: 2,
-        })
-        .and_then(|s| s.as_ref().parse().map_err(|_| Error::ParseError))?;
+    #[test]
+    fn invalid_face_vertex_carries_the_face() {
+        let source = "v 0 0 0\nv 1 0 0\nvn 0 0 1\nf 1//1 2//2 -1//1\n";
+        match parser().parse(source.as_bytes(), ()).unwrap_err() {
+            Error::Located { error, .. } => match *error {
+                Error::InvalidFaceVertex(face) => assert_eq!(
+                    &face[..],
+                    [
+                        FaceIndexPair(0, None, Some(0)),
+                        FaceIndexPair(1, None, Some(1)),
+                        FaceIndexPair(1, None, Some(0)),
+                    ]
+                ),
+                other => panic!("{:?}", other),
+            },
+            other => panic!("{:?}", other),
+        }
 
-    Ok(Vec2::new(first, second))
-}
+        for face in ["f 1 2 4", "f 1/2 2/1 3/1", "f 1//1 2//1 3//1"] {
+            let source = format!("v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\n{}\n", face);
+            let error = parser().parse(source.as_bytes(), ()).unwrap_err();
+            assert_eq!(error.location().unwrap().line(), 5);
+        }
 
-/// Consumes the iterator and parses into `Vec3`.
-pub(crate) fn take_vec3(it: impl IntoIterator<Item = impl AsRef<str>>) -> Result<Vec3<f32>> {
-    let mut it = it.into_iter();
-    let first = it
-        .next()
-        .ok_or(Error::NotEnoughData {
-            found: 0,
-            expected: 2,
-        })
-        .and_then(|s| s.as_ref().parse().map_err(|_| Error::ParseError))?;
-    let second = it
-        .next()
-        .ok_or(Error::NotEnoughData {
-            found: 0,
-            expected: 2,
-        })
-        .and_then(|s| s.as_ref().parse().map_err(|_| Error::ParseError))?;
-    let third = it
-        .next()
-        .ok_or(Error::NotEnoughData {
-            found: 0,
-            expected: 2,
-        })
-        .and_then(|s| s.as_ref().parse().map_err(|_| Error::ParseError))?;
+        for element in ["l 1 3", "p 3"] {
+            let source = format!("v 0 0 0\nv 1 0 0\n{}\n", element);
+            assert!(parser().parse(source.as_bytes(), ()).is_err());
+        }
+    }
+
+    #[test]
+    fn colors_and_texture_ws_are_filled() {
+        let source = "v 0 0 0 1 0 0\nv 1 0 0\nvt 0 0\nvt 1 0 0.5\nf 1/1 2/2 1/1\n";
+        let obj = parser().parse(source.as_bytes(), ()).unwrap();
+        let group = &obj.objects()[0].groups()[0];
+        assert_eq!(
+            group.colors(),
+            Some(&[Rgb::new(1.0, 0.0, 0.0), Rgb::new(1.0, 1.0, 1.0)][..])
+        );
+        assert_eq!(group.texture_ws(), Some(&[0.0, 0.5][..]));
+        assert_eq!(group.texture_uvw(1), Vec3::new(1.0, 0.0, 0.5));
+        assert_eq!(group.vertex_ws(), None);
+    }
 
-    Ok(Vec3::new(first, second, third))
+    #[test]
+    fn lines_and_points_are_kept() {
+        let source =
+            "mtllib a.mtl\nv 0 0 0\nv 1 0 0\nv 1 1 0\nvt 0 0\nusemtl red\nl 1/1 2/1 -1/1\np 1 3\n";
+        let obj = parser().parse(source.as_bytes(), ()).unwrap();
+        let group = &obj.objects()[0].groups()[0];
+        assert_eq!(group.face_index_pairs().len(), 0);
+        assert_eq!(group.line_index_pairs().len(), 1);
+
+        let segments: Vec<_> = group.line_segments().collect();
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[1].0[1].0, Vec3::new(1.0, 1.0, 0.0));
+        assert_eq!(segments[1].0[1].1, Some(Vec2::zero()));
+        assert_eq!(segments[1].1, Some(0));
+
+        let (points, material) = group.points().next().unwrap();
+        let positions: Vec<_> = points.map(|p| p.0).collect();
+        assert_eq!(positions, [Vec3::zero(), Vec3::new(1.0, 1.0, 0.0)]);
+        assert_eq!(material, Some(0));
+    }
+
+    #[test]
+    fn names_keep_spaces_and_groups() {
+        let source = "mtllib a.mtl\no Left Foot Tracker\ng base \\\n  mount\nv 0 0 0\np 1\n";
+        let obj = parser().parse(source.as_bytes(), ()).unwrap();
+        let object = &obj.objects()[0];
+        assert_eq!(object.name(), Some("Left Foot Tracker"));
+        let group = &object.groups()[0];
+        assert_eq!(group.name(), Some("base mount"));
+        assert_eq!(group.names(), [Box::from("base"), Box::from("mount")]);
+    }
 }
@@ -0,0 +1,783 @@
+use crate::{
+    mtl::{Material, MaterialProperty, TextureMap},
+    obj::{ElementIndices, FaceIndexPair, FaceList, Group, Object},
+    Error, Location, Result, SourceFile, WavefrontObj,
+};
+
+use std::{
+    collections::HashMap,
+    io::{prelude::*, BufWriter},
+    path::Path,
+    str::Lines,
+};
+
+use vek::{Rgb, Vec2, Vec3};
+
+impl WavefrontObj {
+    /// Reads ASCII or binary little-endian PLY file.
+    /// `vertex` elements are read with positions, normals, colors and texture UVs
+    /// (`s`/`t`, `u`/`v` or `texture_u`/`texture_v`), `face` elements as faces,
+    /// and `edge` elements as lines. Other elements are skipped.
+    /// If the header has `comment TextureFile`, a material with it as `map_Kd` is added.
+    /// The result has one unnamed object with single group.
+    pub fn read_ply(mut reader: impl Read) -> Result<WavefrontObj> {
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+
+        let (header, body_offset) = Header::parse(&data)?;
+        let mut builder = PlyBuilder::default();
+        match header.format {
+            Format::Ascii => {
+                let body = String::from_utf8_lossy(&data[body_offset..]);
+                let mut values = AsciiValues::new(&body, header.lines);
+                builder.read_elements(&header, &mut values)?;
+            }
+            Format::BinaryLittleEndian => {
+                let mut values = BinaryValues {
+                    data: &data,
+                    position: body_offset,
+                };
+                builder.read_elements(&header, &mut values)?;
+            }
+        }
+        builder.finish(&header)
+    }
+
+    /// Writes all objects as binary little-endian PLY file.
+    /// Faces are triangulated and vertices are split like `Object::to_indexed_mesh`.
+    /// Normals and texture UVs are written only if all vertices have them,
+    /// and colors only if the source has them.
+    /// Lines, points and materials are not written, except the first `map_Kd` as
+    /// `comment TextureFile`.
+    /// The writer will be wrapped with `BufWriter`, so you don't have to do so.
+    pub fn write_ply(&self, writer: impl Write) -> Result<()> {
+        let meshes = self
+            .objects
+            .iter()
+            .map(|o| o.to_indexed_mesh())
+            .collect::<Result<Vec<_>>>()?;
+        let vertex_count: usize = meshes.iter().map(|m| m.vertices.len()).sum();
+        u32::try_from(vertex_count).map_err(|_| Error::TooManyVertices)?;
+        let face_count: usize = meshes.iter().map(|m| m.indices.len() / 3).sum();
+        let has_normals = meshes.iter().all(|m| m.has_normals);
+        let has_uvs = meshes.iter().all(|m| m.has_uvs);
+        let has_colors = meshes.iter().any(|m| m.colors.is_some());
+
+        let mut writer = BufWriter::new(writer);
+        writeln!(writer, "ply")?;
+        writeln!(writer, "format binary_little_endian 1.0")?;
+        let texture = self.materials.iter().find_map(|m| m.diffuse_map());
+        if let Some(texture) = texture {
+            writeln!(writer, "comment TextureFile {}", texture.path.display())?;
+        }
+        writeln!(writer, "element vertex {}", vertex_count)?;
+        for name in ["x", "y", "z"] {
+            writeln!(writer, "property float {}", name)?;
+        }
+        if has_normals {
+            for name in ["nx", "ny", "nz"] {
+                writeln!(writer, "property float {}", name)?;
+            }
+        }
+        if has_uvs {
+            for name in ["s", "t"] {
+                writeln!(writer, "property float {}", name)?;
+            }
+        }
+        if has_colors {
+            for name in ["red", "green", "blue"] {
+                writeln!(writer, "property uchar {}", name)?;
+            }
+        }
+        writeln!(writer, "element face {}", face_count)?;
+        writeln!(writer, "property list uchar uint vertex_indices")?;
+        writeln!(writer, "end_header")?;
+
+        for mesh in meshes.iter() {
+            for (i, vertex) in mesh.vertices.iter().enumerate() {
+                write_floats(&mut writer, &vertex.position)?;
+                if has_normals {
+                    write_floats(&mut writer, &vertex.normal)?;
+                }
+                if has_uvs {
+                    write_floats(&mut writer, &vertex.uv)?;
+                }
+                if has_colors {
+                    let color = mesh.colors.as_ref().map_or([1.0; 3], |c| c[i]);
+                    let color = color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+                    writer.write_all(&color)?;
+                }
+            }
+        }
+
+        let mut offset = 0;
+        for mesh in meshes.iter() {
+            for triangle in mesh.indices.chunks_exact(3) {
+                writer.write_all(&[3])?;
+                for index in triangle {
+                    writer.write_all(&(index + offset).to_le_bytes())?;
+                }
+            }
+            // Checked with the total count above
+            offset += mesh.vertices.len() as u32;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Represents the encoding of PLY body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+}
+
+/// Represents the type of PLY property value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Option<ScalarType> {
+        let scalar_type = match name {
+            "char" | "int8" => ScalarType::I8,
+            "uchar" | "uint8" => ScalarType::U8,
+            "short" | "int16" => ScalarType::I16,
+            "ushort" | "uint16" => ScalarType::U16,
+            "int" | "int32" => ScalarType::I32,
+            "uint" | "uint32" => ScalarType::U32,
+            "float" | "float32" => ScalarType::F32,
+            "double" | "float64" => ScalarType::F64,
+            _ => return None,
+        };
+        Some(scalar_type)
+    }
+
+    /// The scale to convert integer color values into `[0, 1]`, which is the maximum value.
+    /// Returns `None` for floats.
+    fn color_scale(self) -> Option<f64> {
+        let scale = match self {
+            ScalarType::I8 => i8::MAX.into(),
+            ScalarType::U8 => u8::MAX.into(),
+            ScalarType::I16 => i16::MAX.into(),
+            ScalarType::U16 => u16::MAX.into(),
+            ScalarType::I32 => i32::MAX.into(),
+            ScalarType::U32 => u32::MAX.into(),
+            ScalarType::F32 | ScalarType::F64 => return None,
+        };
+        Some(scale)
+    }
+}
+
+/// Represents a PLY property.
+#[derive(Debug, Clone)]
+struct Property {
+    name: Box<str>,
+    value_type: ScalarType,
+
+    /// The type of list length, if this property is a list.
+    count_type: Option<ScalarType>,
+}
+
+/// Represents a PLY element definition.
+#[derive(Debug, Clone)]
+struct Element {
+    name: Box<str>,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    fn property(&self, name: &str) -> Option<usize> {
+        self.properties.iter().position(|p| &*p.name == name)
+    }
+
+    /// Returns the indices of properties, only if all of them are defined.
+    fn properties<const N: usize>(&self, names: [&str; N]) -> Option<[usize; N]> {
+        let mut indices = [0; N];
+        for (index, name) in indices.iter_mut().zip(names) {
+            *index = self.property(name)?;
+        }
+        Some(indices)
+    }
+}
+
+/// Represents the PLY header.
+#[derive(Debug, Clone)]
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+    texture_file: Option<Box<str>>,
+
+    /// The number of header lines.
+    lines: usize,
+}
+
+impl Header {
+    /// Parses the header.
+    /// Returns the header and the offset of the body.
+    fn parse(data: &[u8]) -> Result<(Header, usize)> {
+        let mut format = None;
+        let mut elements: Vec<Element> = vec![];
+        let mut texture_file = None;
+        let mut offset = 0;
+        let mut line_number = 0;
+        loop {
+            let rest = &data[offset..];
+            let length = match rest.iter().position(|b| *b == b'\n') {
+                Some(l) => l + 1,
+                None => return Err(Error::UnsupportedFormat("PLY without end_header".into())),
+            };
+            let line = String::from_utf8_lossy(&rest[..length]);
+            offset += length;
+            line_number += 1;
+
+            let location = || Location::new(SourceFile::Ply, line_number, &line);
+            let tokens: Vec<_> = line.split_ascii_whitespace().collect();
+            match tokens[..] {
+                ["ply"] if line_number == 1 => (),
+                _ if line_number == 1 => {
+                    return Err(Error::UnsupportedFormat("not a PLY file".into()).at(location()))
+                }
+                ["format", "ascii", _] => format = Some(Format::Ascii),
+                ["format", "binary_little_endian", _] => format = Some(Format::BinaryLittleEndian),
+                ["format", f, ..] => {
+                    return Err(Error::UnsupportedFormat(f.to_string()).at(location()))
+                }
+                ["comment", "TextureFile", ..] => {
+                    let file = line.trim().splitn(3, char::is_whitespace).nth(2);
+                    texture_file = file.map(|f| f.trim().into());
+                }
+                ["comment", ..] | ["obj_info", ..] | [] => (),
+                ["element", name, count] => elements.push(Element {
+                    name: name.into(),
+                    count: count
+                        .parse()
+                        .map_err(|_| Error::ParseError(count.to_string()).at(location()))?,
+                    properties: vec![],
+                }),
+                ["property", "list", count_type, value_type, name] => {
+                    let property = Property {
+                        name: name.into(),
+                        value_type: scalar_type(value_type).map_err(|e| e.at(location()))?,
+                        count_type: Some(scalar_type(count_type).map_err(|e| e.at(location()))?),
+                    };
+                    push_property(&mut elements, property).map_err(|e| e.at(location()))?;
+                }
+                ["property", value_type, name] => {
+                    let property = Property {
+                        name: name.into(),
+                        value_type: scalar_type(value_type).map_err(|e| e.at(location()))?,
+                        count_type: None,
+                    };
+                    push_property(&mut elements, property).map_err(|e| e.at(location()))?;
+                }
+                ["end_header"] => break,
+                [keyword, ..] => return Err(Error::ParseError(keyword.to_string()).at(location())),
+            }
+        }
+
+        let format = format.ok_or_else(|| Error::UnsupportedFormat("PLY without format".into()))?;
+        let header = Header {
+            format,
+            elements,
+            texture_file,
+            lines: line_number,
+        };
+        Ok((header, offset))
+    }
+}
+
+fn scalar_type(name: &str) -> Result<ScalarType> {
+    ScalarType::parse(name).ok_or_else(|| Error::UnsupportedFormat(name.to_string()))
+}
+
+fn push_property(elements: &mut [Element], property: Property) -> Result<()> {
+    match elements.last_mut() {
+        Some(element) => {
+            element.properties.push(property);
+            Ok(())
+        }
+        None => Err(Error::UnsupportedFormat("property without element".into())),
+    }
+}
+
+/// Reads values in PLY body.
+trait Values {
+    fn read(&mut self, value_type: ScalarType) -> Result<f64>;
+}
+
+/// Reads values from ASCII body, which are separated by whitespaces.
+struct AsciiValues<'a> {
+    lines: Lines<'a>,
+    line: &'a str,
+    rest: &'a str,
+    line_number: usize,
+}
+
+impl<'a> AsciiValues<'a> {
+    fn new(body: &'a str, header_lines: usize) -> AsciiValues<'a> {
+        AsciiValues {
+            lines: body.lines(),
+            line: "",
+            rest: "",
+            line_number: header_lines,
+        }
+    }
+}
+
+impl<'a> Values for AsciiValues<'a> {
+    fn read(&mut self, _: ScalarType) -> Result<f64> {
+        loop {
+            let trimmed = self.rest.trim_start();
+            if !trimmed.is_empty() {
+                let end = trimmed
+                    .find(|c: char| c.is_ascii_whitespace())
+                    .unwrap_or(trimmed.len());
+                let (token, rest) = trimmed.split_at(end);
+                self.rest = rest;
+                return token.parse().map_err(|_| {
+                    let location = Location::new(SourceFile::Ply, self.line_number, self.line);
+                    Error::ParseError(token.to_string()).at(location)
+                });
+            }
+
+            self.line = self.lines.next().ok_or(Error::NotEnoughData {
+                found: 0,
+                expected: 1,
+            })?;
+            self.rest = self.line;
+            self.line_number += 1;
+        }
+    }
+}
+
+/// Reads values from binary little-endian body.
+struct BinaryValues<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BinaryValues<'a> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let end = self.position + N;
+        let bytes = self
+            .data
+            .get(self.position..end)
+            .ok_or(Error::NotEnoughData {
+                found: self.data.len(),
+                expected: end,
+            })?;
+        self.position = end;
+        Ok(bytes.try_into().expect("Should have N bytes"))
+    }
+}
+
+impl<'a> Values for BinaryValues<'a> {
+    fn read(&mut self, value_type: ScalarType) -> Result<f64> {
+        let value = match value_type {
+            ScalarType::I8 => i8::from_le_bytes(self.take()?) as f64,
+            ScalarType::U8 => u8::from_le_bytes(self.take()?) as f64,
+            ScalarType::I16 => i16::from_le_bytes(self.take()?) as f64,
+            ScalarType::U16 => u16::from_le_bytes(self.take()?) as f64,
+            ScalarType::I32 => i32::from_le_bytes(self.take()?) as f64,
+            ScalarType::U32 => u32::from_le_bytes(self.take()?) as f64,
+            ScalarType::F32 => f32::from_le_bytes(self.take()?) as f64,
+            ScalarType::F64 => f64::from_le_bytes(self.take()?),
+        };
+        Ok(value)
+    }
+}
+
+/// Collects vertex data and elements.
+#[derive(Debug, Default)]
+struct PlyBuilder {
+    vertices: Vec<Vec3<f32>>,
+    normals: Vec<Vec3<f32>>,
+    uvs: Vec<Vec2<f32>>,
+    colors: Vec<Rgb<f32>>,
+    faces: Vec<Box<[usize]>>,
+    edges: Vec<[usize; 2]>,
+}
+
+impl PlyBuilder {
+    /// Reads all elements in the body.
+    fn read_elements(&mut self, header: &Header, values: &mut impl Values) -> Result<()> {
+        let mut scalars = vec![];
+        let mut list = vec![];
+        for element in header.elements.iter() {
+            let mapping = ElementMapping::new(element);
+            let list_property = match mapping {
+                ElementMapping::Face(i) => Some(i),
+                _ => None,
+            };
+            scalars.resize(element.properties.len(), 0.0);
+
+            for _ in 0..element.count {
+                list.clear();
+                for (i, property) in element.properties.iter().enumerate() {
+                    match property.count_type {
+                        Some(count_type) => {
+                            let count = values.read(count_type)? as usize;
+                            let keep = Some(i) == list_property;
+                            for _ in 0..count {
+                                let value = values.read(property.value_type)?;
+                                if keep {
+                                    list.push(index(value));
+                                }
+                            }
+                        }
+                        None => scalars[i] = values.read(property.value_type)?,
+                    }
+                }
+                self.push_element(&mapping, &scalars, &list);
+            }
+        }
+        Ok(())
+    }
+
+    fn push_element(&mut self, mapping: &ElementMapping, scalars: &[f64], list: &[usize]) {
+        let vector = |[x, y, z]: [usize; 3]| {
+            Vec3::new(scalars[x] as f32, scalars[y] as f32, scalars[z] as f32)
+        };
+        match mapping {
+            ElementMapping::Vertex {
+                position,
+                normal,
+                uv,
+                color,
+            } => {
+                self.vertices.push(vector(*position));
+                if let Some(normal) = normal {
+                    self.normals.push(vector(*normal));
+                }
+                if let Some([u, v]) = uv {
+                    self.uvs
+                        .push(Vec2::new(scalars[*u] as f32, scalars[*v] as f32));
+                }
+                if let Some((color, scale)) = color {
+                    let color = vector(*color) / *scale as f32;
+                    self.colors.push(Rgb::new(color.x, color.y, color.z));
+                }
+            }
+            ElementMapping::Face(_) => self.faces.push(list.into()),
+            ElementMapping::Edge([a, b]) => {
+                self.edges.push([index(scalars[*a]), index(scalars[*b])])
+            }
+            ElementMapping::Other => (),
+        }
+    }
+
+    fn finish(self, header: &Header) -> Result<WavefrontObj> {
+        let vertex_count = self.vertices.len();
+        let has_uvs = !self.uvs.is_empty();
+        let has_normals = !self.normals.is_empty();
+        let material = header.texture_file.as_ref().map(|_| 0);
+        let pairs = |indices: &[usize]| -> Result<Box<[FaceIndexPair]>> {
+            let pairs: Box<[_]> = indices
+                .iter()
+                .map(|&i| FaceIndexPair(i, has_uvs.then_some(i), has_normals.then_some(i)))
+                .collect();
+            if indices.iter().any(|&i| i >= vertex_count) {
+                return Err(Error::InvalidFaceVertex(pairs));
+            }
+            Ok(pairs)
+        };
+
+        let mut faces = FaceList::default();
+        for face in self.faces.iter() {
+            if face.len() < 3 {
+                return Err(Error::NotEnoughData {
+                    found: face.len(),
+                    expected: 3,
+                });
+            }
+            faces.push(&pairs(face)?, material, None);
+        }
+        let mut lines: Vec<ElementIndices> = vec![];
+        for edge in self.edges.iter() {
+            lines.push((pairs(edge)?, material));
+        }
+
+        let group = Group {
+            name: None,
+            names: Box::default(),
+            vertices: self.vertices.into(),
+            texture_uvs: self.uvs.into(),
+            normals: self.normals.into(),
+            colors: (!self.colors.is_empty()).then(|| self.colors.into()),
+            vertex_ws: None,
+            texture_ws: None,
+            face_index_pairs: faces,
+            lines: lines.into_boxed_slice(),
+            points: Box::default(),
+        };
+        let mut objects = vec![];
+        if !group.face_index_pairs.is_empty() || !group.lines.is_empty() {
+            objects.push(Object {
+                name: None,
+                groups: vec![group].into_boxed_slice(),
+            });
+        }
+
+        let materials = header.texture_file.as_ref().map(|file| {
+            let path = Path::new(&**file);
+            let name = path
+                .file_stem()
+                .map_or(file.clone(), |s| s.to_string_lossy().into());
+            let texture = MaterialProperty::Texture(TextureMap::new(path));
+            Material {
+                name,
+                properties: HashMap::from([("map_Kd".to_string(), texture)]),
+            }
+        });
+
+        Ok(WavefrontObj {
+            objects: objects.into_boxed_slice(),
+            materials: materials.into_iter().collect(),
+            parameter_vertices: Box::default(),
+            diagnostics: Box::default(),
+        })
+    }
+}
+
+/// Converts the value into vertex index.
+/// Negative values are turned into invalid index, instead of zero.
+fn index(value: f64) -> usize {
+    if value < 0.0 {
+        usize::MAX
+    } else {
+        value as usize
+    }
+}
+
+/// Represents how properties of an element are used.
+#[derive(Debug, Clone)]
+enum ElementMapping {
+    Vertex {
+        position: [usize; 3],
+        normal: Option<[usize; 3]>,
+        uv: Option<[usize; 2]>,
+        color: Option<([usize; 3], f64)>,
+    },
+    Face(usize),
+    Edge([usize; 2]),
+    Other,
+}
+
+impl ElementMapping {
+    fn new(element: &Element) -> ElementMapping {
+        match &*element.name {
+            "vertex" => {
+                let position = match element.properties(["x", "y", "z"]) {
+                    Some(p) => p,
+                    None => return ElementMapping::Other,
+                };
+                let normal = element.properties(["nx", "ny", "nz"]);
+                let uv = element
+                    .properties(["s", "t"])
+                    .or_else(|| element.properties(["u", "v"]))
+                    .or_else(|| element.properties(["texture_u", "texture_v"]));
+                let color = element
+                    .properties(["red", "green", "blue"])
+                    .or_else(|| {
+                        element.properties(["diffuse_red", "diffuse_green", "diffuse_blue"])
+                    })
+                    .map(|c| {
+                        // Float colors are already in `[0, 1]`
+                        let scale = element.properties[c[0]].value_type.color_scale();
+                        (c, scale.unwrap_or(1.0))
+                    });
+                ElementMapping::Vertex {
+                    position,
+                    normal,
+                    uv,
+                    color,
+                }
+            }
+            "face" => match element
+                .property("vertex_indices")
+                .or_else(|| element.property("vertex_index"))
+                .filter(|&i| element.properties[i].count_type.is_some())
+            {
+                Some(i) => ElementMapping::Face(i),
+                None => ElementMapping::Other,
+            },
+            "edge" => match element.properties(["vertex1", "vertex2"]) {
+                Some(e) => ElementMapping::Edge(e),
+                None => ElementMapping::Other,
+            },
+            _ => ElementMapping::Other,
+        }
+    }
+}
+
+fn write_floats(writer: &mut impl Write, values: &[f32]) -> Result<()> {
+    for value in values {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FaceIndexPair, Parser, WavefrontObj};
+
+    use std::io::Cursor;
+
+    use vek::{Rgb, Vec3};
+
+    const QUAD: &str = "\
+ply
+format ascii 1.0
+comment TextureFile quad.png
+element vertex 4
+property float x
+property float y
+property float z
+property float s
+property float t
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+element edge 1
+property int vertex1
+property int vertex2
+end_header
+0 0 0 0 0 255 0 0
+1 0 0 1 0 0 255 0
+1 1 0 1 1 0 0 255
+0 1 0 0 1 255 255 255
+4 0 1 2 3
+0 2
+";
+
+    #[test]
+    fn ascii_ply_is_read() {
+        let obj = WavefrontObj::read_ply(QUAD.as_bytes()).unwrap();
+        let group = &obj.objects()[0].groups()[0];
+        assert_eq!(group.vertices()[2], Vec3::new(1.0, 1.0, 0.0));
+        assert_eq!(group.texture_uvs().len(), 4);
+        assert_eq!(group.colors().unwrap()[1], Rgb::new(0.0, 1.0, 0.0));
+
+        let (face, material, _) = group.face_index_pairs().get(0).unwrap();
+        assert_eq!(face.len(), 4);
+        assert_eq!(face[3], FaceIndexPair(3, Some(3), None));
+        assert_eq!(material, Some(0));
+        assert_eq!(group.line_index_pairs()[0].0.len(), 2);
+
+        let texture_map = obj.materials()[0].diffuse_map().unwrap();
+        assert_eq!(texture_map.path().to_str(), Some("quad.png"));
+    }
+
+    #[test]
+    fn lists_are_not_carried_over_elements() {
+        let source = "\
+ply
+format ascii 1.0
+element vertex 3
+property float x
+property float y
+property float z
+element tristrips 1
+property list int int vertex_indices
+element face 2
+property uchar flags
+end_header
+0 0 0
+1 0 0
+0 1 0
+3 0 1 2
+0
+0
+";
+        let obj = WavefrontObj::read_ply(source.as_bytes()).unwrap();
+        assert!(obj.objects().is_empty());
+    }
+
+    #[test]
+    fn colors_are_scaled_by_type() {
+        let cases = [
+            ("ushort", "65535 0 32768", 0.5),
+            ("char", "127 0 -127", -1.0),
+            ("float", "1 0 0.25", 0.25),
+        ];
+        for (value_type, color, blue) in cases {
+            let source = format!(
+                "ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nproperty float y\n\
+                 property float z\nproperty {0} red\nproperty {0} green\nproperty {0} blue\n\
+                 element face 1\nproperty list uchar int vertex_indices\nend_header\n\
+                 0 0 0 {1}\n3 0 0 0\n",
+                value_type, color
+            );
+            let obj = WavefrontObj::read_ply(source.as_bytes()).unwrap();
+            let color = obj.objects()[0].groups()[0].colors().unwrap()[0];
+            assert_eq!((color.r, color.g), (1.0, 0.0), "{}", value_type);
+            assert!((color.b - blue).abs() < 1e-4, "{}", value_type);
+        }
+    }
+
+    #[test]
+    fn invalid_indices_are_errors() {
+        let source = QUAD.replace("4 0 1 2 3", "4 0 1 2 4");
+        assert!(WavefrontObj::read_ply(source.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn written_ply_is_read_back() {
+        let obj = WavefrontObj::read_ply(QUAD.as_bytes()).unwrap();
+        let mut ply = vec![];
+        obj.write_ply(&mut ply).unwrap();
+
+        let read = WavefrontObj::read_ply(&ply[..]).unwrap();
+        let source = &obj.objects()[0].groups()[0];
+        let group = &read.objects()[0].groups()[0];
+        assert_eq!(group.vertices(), source.vertices());
+        assert_eq!(group.texture_uvs(), source.texture_uvs());
+        assert_eq!(group.colors(), source.colors());
+        assert_eq!(group.face_index_pairs().len(), 2);
+        assert!(group.line_index_pairs().is_empty());
+
+        let texture_map = read.materials()[0].diffuse_map().unwrap();
+        assert_eq!(texture_map.path().to_str(), Some("quad.png"));
+    }
+
+    #[test]
+    fn objects_are_written_with_offset_indices() {
+        let source = "\
+o a
+v 0 0 0
+v 1 0 0
+v 0 1 0
+vn 0 0 1
+f 1//1 2//1 3//1
+o b
+f 3//1 2//1 1//1
+";
+        let mut parser = Parser::new(|_, _: &()| Ok(Cursor::new(vec![])));
+        let obj = parser.parse(source.as_bytes(), ()).unwrap();
+        let mut ply = vec![];
+        obj.write_ply(&mut ply).unwrap();
+
+        let read = WavefrontObj::read_ply(&ply[..]).unwrap();
+        let group = &read.objects()[0].groups()[0];
+        assert_eq!(group.vertices().len(), 6);
+        assert_eq!(group.normals(), [Vec3::unit_z(); 6]);
+        let (face, _, _) = group.face_index_pairs().get(1).unwrap();
+        let indices: Vec<_> = face.iter().map(|p| p.0).collect();
+        assert_eq!(indices, [3, 4, 5]);
+        assert_eq!(group.vertices()[3], Vec3::new(0.0, 1.0, 0.0));
+    }
+}
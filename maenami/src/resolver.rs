@@ -0,0 +1,271 @@
+use crate::{Error, Result};
+
+use std::{
+    collections::HashMap,
+    fs::{canonicalize, File},
+    io::{self, prelude::*, Cursor, ErrorKind},
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+/// Resolves MTL files specified in `mtllib`.
+/// Pass it to `Parser::with_resolver`.
+pub trait Resolver {
+    /// The reader of resolved files.
+    type Reader: Read;
+
+    /// Returns the reader of the file specified in `mtllib`.
+    fn resolve(&self, path: &Path) -> Result<Self::Reader>;
+}
+
+/// Resolves MTL files on the filesystem, relative to the directory of OBJ file.
+/// Use it as `Parser::with_resolver(resolver)`.
+#[derive(Debug, Clone)]
+pub struct FileResolver {
+    base_directory: PathBuf,
+    root: Option<PathBuf>,
+}
+
+impl FileResolver {
+    /// Creates an instance of `FileResolver`.
+    /// Paths in `mtllib` are resolved relative to `base_directory`.
+    pub fn new(base_directory: impl Into<PathBuf>) -> FileResolver {
+        FileResolver {
+            base_directory: base_directory.into(),
+            root: None,
+        }
+    }
+
+    /// Creates an instance of `FileResolver` resolving relative to the directory of OBJ file.
+    pub fn for_obj(obj_path: impl AsRef<Path>) -> FileResolver {
+        let base_directory = obj_path.as_ref().parent().unwrap_or_else(|| Path::new(""));
+        FileResolver::new(base_directory)
+    }
+
+    /// Confines resolved files to `root` and its subdirectories.
+    /// Paths escaping `root` (with `..`, absolute paths or symbolic links)
+    /// are rejected with `Error::PathNotAllowed`.
+    /// Both `root` and the base directory are canonicalized here, so they must exist.
+    pub fn confine_to(mut self, root: impl AsRef<Path>) -> Result<FileResolver> {
+        self.root = Some(canonicalize(root)?);
+        self.base_directory = canonicalize(&self.base_directory)?;
+        Ok(self)
+    }
+
+    /// The directory which paths are resolved relative to.
+    pub fn base_directory(&self) -> &Path {
+        &self.base_directory
+    }
+
+    /// The root directory which files are confined to, if any.
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+
+    /// Opens the file specified in `mtllib`.
+    /// When confined, the path is checked lexically before accessing the filesystem,
+    /// and checked again after resolving symbolic links.
+    pub fn resolve(&self, path: &Path) -> Result<File> {
+        let not_found = |e: io::Error| match e.kind() {
+            ErrorKind::NotFound => Error::PathNotFound(path.display().to_string()),
+            _ => Error::IoError(e),
+        };
+        let joined = self.base_directory.join(path);
+        if let Some(root) = &self.root {
+            if !normalize_lexically(&joined).starts_with(root) {
+                return Err(Error::PathNotAllowed(path.display().to_string()));
+            }
+            let canonical_path = canonicalize(&joined).map_err(not_found)?;
+            if !canonical_path.starts_with(root) {
+                return Err(Error::PathNotAllowed(path.display().to_string()));
+            }
+            return File::open(canonical_path).map_err(not_found);
+        }
+
+        File::open(&joined).map_err(not_found)
+    }
+}
+
+impl Resolver for FileResolver {
+    type Reader = File;
+
+    fn resolve(&self, path: &Path) -> Result<File> {
+        FileResolver::resolve(self, path)
+    }
+}
+
+/// Resolves MTL files from contents registered in memory.
+/// Paths are compared after removing `.` and `..`, and paths escaping
+/// the top level are rejected with `Error::PathNotAllowed`.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryResolver {
+    files: HashMap<PathBuf, Arc<[u8]>>,
+}
+
+impl MemoryResolver {
+    /// Creates an empty instance of `MemoryResolver`.
+    pub fn new() -> MemoryResolver {
+        MemoryResolver::default()
+    }
+
+    /// Registers the contents of file.
+    pub fn insert(&mut self, path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) {
+        let path = normalize(path.as_ref()).unwrap_or_else(|| path.as_ref().into());
+        self.files.insert(path, contents.into().into());
+    }
+
+    /// Registers the contents of file, and returns itself.
+    pub fn with(mut self, path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) -> MemoryResolver {
+        self.insert(path, contents);
+        self
+    }
+
+    /// Returns the reader of the file specified in `mtllib`.
+    pub fn resolve(&self, path: &Path) -> Result<Cursor<Arc<[u8]>>> {
+        let normalized =
+            normalize(path).ok_or_else(|| Error::PathNotAllowed(path.display().to_string()))?;
+        match self.files.get(&normalized) {
+            Some(contents) => Ok(Cursor::new(contents.clone())),
+            None => Err(Error::PathNotFound(path.display().to_string())),
+        }
+    }
+}
+
+impl Resolver for MemoryResolver {
+    type Reader = Cursor<Arc<[u8]>>;
+
+    fn resolve(&self, path: &Path) -> Result<Cursor<Arc<[u8]>>> {
+        MemoryResolver::resolve(self, path)
+    }
+}
+
+/// Removes `.` and `..` from the relative path.
+/// Returns `None` if the path is absolute or escapes the top level.
+fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(c) => normalized.push(c),
+            Component::CurDir => (),
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(normalized)
+}
+
+/// Removes `.` and `..` from the path without accessing the filesystem.
+/// `..` at the root stays at the root.
+fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            c => normalized.push(c),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    use std::{env::temp_dir, fs, io::Read, process};
+
+    fn read(mut reader: impl Read) -> String {
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents).unwrap();
+        contents
+    }
+
+    #[test]
+    fn memory_resolver_normalizes_paths() {
+        let resolver = MemoryResolver::new().with("materials/a.mtl", "newmtl a\n");
+        let resolved = resolver.resolve(Path::new("./materials/../materials/a.mtl"));
+        assert_eq!(read(resolved.unwrap()), "newmtl a\n");
+
+        assert!(matches!(
+            resolver.resolve(Path::new("b.mtl")),
+            Err(Error::PathNotFound(_))
+        ));
+        for escaping in ["../a.mtl", "/materials/a.mtl"] {
+            assert!(matches!(
+                resolver.resolve(Path::new(escaping)),
+                Err(Error::PathNotAllowed(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn materials_of_all_libraries_are_loaded() {
+        let resolver = MemoryResolver::new()
+            .with("a.mtl", "newmtl a\n")
+            .with("b.mtl", "newmtl b\n");
+        let mut parser = Parser::with_resolver(resolver);
+        let obj = parser
+            .parse(&b"mtllib a.mtl\nmtllib b.mtl a.mtl\n"[..], ())
+            .unwrap();
+        let names: Vec<_> = obj.materials().iter().map(|m| m.name()).collect();
+        assert_eq!(names, ["a", "b", "a"]);
+    }
+
+    #[test]
+    fn file_resolver_is_confined_to_root() {
+        let root = temp_dir().join(format!("maenami-resolver-{}", process::id()));
+        let models = root.join("models");
+        fs::create_dir_all(&models).unwrap();
+        fs::write(models.join("a.mtl"), "newmtl a\n").unwrap();
+        fs::write(root.join("secret.mtl"), "newmtl secret\n").unwrap();
+
+        let resolver = FileResolver::for_obj(models.join("a.obj"));
+        assert_eq!(resolver.base_directory(), models);
+        assert_eq!(
+            read(resolver.resolve(Path::new("a.mtl")).unwrap()),
+            "newmtl a\n"
+        );
+        assert!(resolver.resolve(Path::new("../secret.mtl")).is_ok());
+        assert!(matches!(
+            resolver.resolve(Path::new("b.mtl")),
+            Err(Error::PathNotFound(_))
+        ));
+
+        let confined = resolver.confine_to(&models).unwrap();
+        assert!(confined.resolve(Path::new("a.mtl")).is_ok());
+        assert!(matches!(
+            confined.resolve(Path::new("b.mtl")),
+            Err(Error::PathNotFound(_))
+        ));
+        let secret = root.join("secret.mtl");
+        for escaping in [
+            Path::new("../secret.mtl"),
+            Path::new("../missing.mtl"),
+            Path::new("../models/../../missing.mtl"),
+            &secret,
+        ] {
+            assert!(matches!(
+                confined.resolve(escaping),
+                Err(Error::PathNotAllowed(_))
+            ));
+        }
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(root.join("secret.mtl"), models.join("link.mtl")).unwrap();
+            assert!(matches!(
+                confined.resolve(Path::new("link.mtl")),
+                Err(Error::PathNotAllowed(_))
+            ));
+        }
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
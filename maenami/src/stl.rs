@@ -0,0 +1,287 @@
+use crate::{
+    command::take_vec3,
+    obj::{FaceIndexPair, FaceList, Group, Object},
+    Error, Location, Result, SourceFile, WavefrontObj,
+};
+
+use std::{collections::HashMap, io::prelude::*, mem::take, sync::Arc};
+
+use vek::Vec3;
+
+/// The size of binary STL header and triangle count.
+const BINARY_HEADER_SIZE: usize = 84;
+
+/// The size of each triangle in binary STL.
+const BINARY_TRIANGLE_SIZE: usize = 50;
+
+impl WavefrontObj {
+    /// Reads ASCII or binary STL file.
+    /// Each `solid` becomes an object with single group; binary STL has one unnamed object.
+    /// Vertices at identical positions are merged, and facet normals are set to
+    /// all vertices of the facet (zero normals are omitted).
+    pub fn read_stl(mut reader: impl Read) -> Result<WavefrontObj> {
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+
+        // Binary STL may also start with "solid", so check the size and NUL bytes too
+        let binary_size = data
+            .get(80..BINARY_HEADER_SIZE)
+            .map(|c| BINARY_HEADER_SIZE + read_u32(c) as usize * BINARY_TRIANGLE_SIZE);
+        let ascii = data.starts_with(b"solid") && !data.contains(&0);
+        if binary_size != Some(data.len()) && ascii {
+            read_ascii(&String::from_utf8_lossy(&data))
+        } else {
+            read_binary(&data)
+        }
+    }
+}
+
+/// Reads binary STL.
+fn read_binary(data: &[u8]) -> Result<WavefrontObj> {
+    let count = match data.get(80..BINARY_HEADER_SIZE) {
+        Some(c) => read_u32(c) as usize,
+        None => {
+            return Err(Error::NotEnoughData {
+                found: data.len(),
+                expected: BINARY_HEADER_SIZE,
+            })
+        }
+    };
+    let expected = BINARY_HEADER_SIZE + count * BINARY_TRIANGLE_SIZE;
+    if data.len() < expected {
+        return Err(Error::NotEnoughData {
+            found: data.len(),
+            expected,
+        });
+    }
+
+    let mut builder = StlBuilder::default();
+    for triangle in data[BINARY_HEADER_SIZE..expected].chunks_exact(BINARY_TRIANGLE_SIZE) {
+        let vectors: Vec<_> = triangle[..48]
+            .chunks_exact(12)
+            .map(|c| Vec3::new(read_f32(&c[0..4]), read_f32(&c[4..8]), read_f32(&c[8..12])))
+            .collect();
+        builder.push_facet(vectors[0], &vectors[1..]);
+    }
+    builder.commit_solid(None);
+    Ok(builder.finish())
+}
+
+/// Reads ASCII STL.
+fn read_ascii(source: &str) -> Result<WavefrontObj> {
+    let mut builder = StlBuilder::default();
+    let mut name = None;
+    let mut normal = Vec3::zero();
+    let mut positions = vec![];
+    for (i, line) in source.lines().enumerate() {
+        let location = || Location::new(SourceFile::Stl, i + 1, line);
+        let mut tokens = line.split_ascii_whitespace();
+        let keyword = match tokens.next() {
+            Some(k) => k,
+            None => continue,
+        };
+
+        match keyword {
+            "solid" => {
+                let rest = line.trim_start()["solid".len()..].trim();
+                name = (!rest.is_empty()).then(|| rest.into());
+            }
+            "facet" => {
+                if tokens.next() != Some("normal") {
+                    return Err(Error::ParseError(line.trim().to_string()).at(location()));
+                }
+                normal = take_vec3(tokens).map_err(|e| e.at(location()))?;
+                positions.clear();
+            }
+            "vertex" => positions.push(take_vec3(tokens).map_err(|e| e.at(location()))?),
+            "endfacet" => {
+                if positions.len() < 3 {
+                    let error = Error::NotEnoughData {
+                        found: positions.len(),
+                        expected: 3,
+                    };
+                    return Err(error.at(location()));
+                }
+                builder.push_facet(normal, &positions);
+            }
+            "endsolid" => builder.commit_solid(name.take()),
+            "outer" | "endloop" => (),
+            _ => return Err(Error::ParseError(keyword.to_string()).at(location())),
+        }
+    }
+
+    // Some exporters omit `endsolid`
+    builder.commit_solid(name);
+    Ok(builder.finish())
+}
+
+/// Collects facets and merges identical vertices.
+#[derive(Debug, Default)]
+struct StlBuilder {
+    vertices: Vec<Vec3<f32>>,
+    normals: Vec<Vec3<f32>>,
+    vertex_indices: HashMap<[u32; 3], usize>,
+    normal_indices: HashMap<[u32; 3], usize>,
+    faces: FaceList,
+    objects: Vec<Object>,
+}
+
+impl StlBuilder {
+    /// Appends a facet.
+    fn push_facet(&mut self, normal: Vec3<f32>, positions: &[Vec3<f32>]) {
+        let normal = (normal != Vec3::zero())
+            .then(|| intern(&mut self.normals, &mut self.normal_indices, normal));
+        let face: Vec<_> = positions
+            .iter()
+            .map(|p| {
+                let v = intern(&mut self.vertices, &mut self.vertex_indices, *p);
+                FaceIndexPair(v, None, normal)
+            })
+            .collect();
+        self.faces.push(&face, None, None);
+    }
+
+    /// Finishes the current solid.
+    fn commit_solid(&mut self, name: Option<Box<str>>) {
+        if self.faces.is_empty() {
+            return;
+        }
+
+        let group = Group {
+            name: None,
+            names: Box::default(),
+            vertices: Default::default(),
+            texture_uvs: Default::default(),
+            normals: Default::default(),
+            colors: None,
+            vertex_ws: None,
+            texture_ws: None,
+            face_index_pairs: take(&mut self.faces),
+            lines: Box::default(),
+            points: Box::default(),
+        };
+        self.objects.push(Object {
+            name,
+            groups: vec![group].into_boxed_slice(),
+        });
+    }
+
+    fn finish(mut self) -> WavefrontObj {
+        let vertices: Arc<[_]> = take(&mut self.vertices).into();
+        let normals: Arc<[_]> = take(&mut self.normals).into();
+        for group in self.objects.iter_mut().flat_map(|o| o.groups.iter_mut()) {
+            group.vertices = vertices.clone();
+            group.normals = normals.clone();
+        }
+
+        WavefrontObj {
+            objects: self.objects.into_boxed_slice(),
+            materials: Box::default(),
+            parameter_vertices: Box::default(),
+            diagnostics: Box::default(),
+        }
+    }
+}
+
+/// Returns the index of the vector, appending it if not yet.
+fn intern(
+    pool: &mut Vec<Vec3<f32>>,
+    indices: &mut HashMap<[u32; 3], usize>,
+    vector: Vec3<f32>,
+) -> usize {
+    // Adding zero turns -0.0 into 0.0
+    let key = (vector + 0.0).map(f32::to_bits).into_array();
+    *indices.entry(key).or_insert_with(|| {
+        pool.push(vector);
+        pool.len() - 1
+    })
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes.try_into().expect("Should be 4 bytes"))
+}
+
+fn read_f32(bytes: &[u8]) -> f32 {
+    f32::from_le_bytes(bytes.try_into().expect("Should be 4 bytes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FaceIndexPair, WavefrontObj};
+
+    use vek::Vec3;
+
+    const ASCII: &str = "\
+solid Tracker Mount
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 0 1 0
+    endloop
+  endfacet
+  facet normal 0 0 0
+    outer loop
+      vertex 1 0 0
+      vertex 1 1 0
+      vertex 0 1 0
+    endloop
+  endfacet
+endsolid Tracker Mount
+";
+
+    /// Makes binary STL of facets, with the header starting with "solid".
+    fn binary(facets: &[[Vec3<f32>; 4]]) -> Vec<u8> {
+        let mut data = b"solid binary".to_vec();
+        data.resize(80, 0);
+        data.extend_from_slice(&(facets.len() as u32).to_le_bytes());
+        for facet in facets {
+            for vector in facet {
+                for value in vector.into_array() {
+                    data.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            data.extend_from_slice(&[0, 0]);
+        }
+        data
+    }
+
+    #[test]
+    fn ascii_stl_is_read() {
+        let obj = WavefrontObj::read_stl(ASCII.as_bytes()).unwrap();
+        let object = &obj.objects()[0];
+        assert_eq!(object.name(), Some("Tracker Mount"));
+
+        let group = &object.groups()[0];
+        assert_eq!(group.vertices().len(), 4);
+        assert_eq!(group.normals(), [Vec3::unit_z()]);
+        let (first, _, _) = group.face_index_pairs().get(0).unwrap();
+        assert_eq!(first[1], FaceIndexPair(1, None, Some(0)));
+        let (second, _, _) = group.face_index_pairs().get(1).unwrap();
+        assert_eq!(second[0], FaceIndexPair(1, None, None));
+    }
+
+    #[test]
+    fn binary_stl_is_read() {
+        let facet = [Vec3::unit_z(), Vec3::zero(), Vec3::unit_x(), Vec3::unit_y()];
+        let data = binary(&[facet, facet]);
+        let obj = WavefrontObj::read_stl(&data[..]).unwrap();
+        let group = &obj.objects()[0].groups()[0];
+        assert_eq!(obj.objects()[0].name(), None);
+        assert_eq!(group.vertices(), &facet[1..]);
+        assert_eq!(group.face_index_pairs().len(), 2);
+
+        assert!(WavefrontObj::read_stl(&data[..data.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn broken_ascii_stl_has_location() {
+        let source = ASCII.replace("vertex 1 1 0", "vertex 1 1");
+        let error = WavefrontObj::read_stl(source.as_bytes()).unwrap_err();
+        assert_eq!(error.location().unwrap().line(), 12);
+
+        let source = ASCII.replace("facet normal 0 0 1", "facet 0 0 1");
+        let error = WavefrontObj::read_stl(source.as_bytes()).unwrap_err();
+        assert_eq!(error.location().unwrap().line(), 2);
+    }
+}
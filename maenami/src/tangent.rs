@@ -0,0 +1,201 @@
+use crate::{
+    mesh::{IndexedMesh, MeshVertex},
+    Error, Result,
+};
+
+use std::collections::HashMap;
+
+use bevy_mikktspace::Geometry;
+use vek::Vec3;
+
+impl IndexedMesh {
+    /// Generates tangents for normal mapping with MikkTSpace, and returns the mesh having them
+    /// in `tangents()`. `xyz` is the tangent orthogonal to the normal, and `w` is the sign
+    /// of bitangent, so that bitangent equals `w * cross(normal, tangent)`.
+    ///
+    /// MikkTSpace generates tangents for each corner of triangles, so vertices whose corners
+    /// get different tangents are split; the vertex buffer may grow, but draw ranges are kept.
+    /// Requires texture UVs and normals on all vertices (see `Group::generate_normals`).
+    pub fn generate_tangents(&self) -> Result<IndexedMesh> {
+        if !self.has_uvs {
+            return Err(Error::MissingTextureUv);
+        }
+        if !self.has_normals {
+            return Err(Error::MissingNormal);
+        }
+
+        let mut geometry = MikkGeometry {
+            mesh: self,
+            tangents: vec![None; self.indices.len()],
+        };
+        // Fails only if there are no non-degenerate triangles, then defaults are used
+        bevy_mikktspace::generate_tangents(&mut geometry);
+
+        let mut split_indices = HashMap::new();
+        let mut vertices = vec![];
+        let mut colors = vec![];
+        let mut tangents = vec![];
+        let mut indices = Vec::with_capacity(self.indices.len());
+        for (&index, tangent) in self.indices.iter().zip(geometry.tangents) {
+            let vertex = self.vertices[index as usize];
+            let tangent = tangent.unwrap_or_else(|| {
+                let normal = perpendicular(vertex.normal.into());
+                [normal.x, normal.y, normal.z, 1.0]
+            });
+
+            let key = (index, tangent.map(f32::to_bits));
+            let split_index = match split_indices.get(&key) {
+                Some(&i) => i,
+                None => {
+                    let i = u32::try_from(vertices.len()).map_err(|_| Error::TooManyVertices)?;
+                    vertices.push(vertex);
+                    if let Some(source) = &self.colors {
+                        colors.push(source[index as usize]);
+                    }
+                    tangents.push(tangent);
+                    split_indices.insert(key, i);
+                    i
+                }
+            };
+            indices.push(split_index);
+        }
+
+        Ok(IndexedMesh {
+            vertices: vertices.into_boxed_slice(),
+            indices: indices.into_boxed_slice(),
+            draw_ranges: self.draw_ranges.clone(),
+            colors: self.colors.as_ref().map(|_| colors.into_boxed_slice()),
+            tangents: Some(tangents.into_boxed_slice()),
+            has_uvs: self.has_uvs,
+            has_normals: self.has_normals,
+        })
+    }
+}
+
+/// Provides triangles of `IndexedMesh` to MikkTSpace, and receives tangents of corners.
+struct MikkGeometry<'a> {
+    mesh: &'a IndexedMesh,
+    tangents: Vec<Option<[f32; 4]>>,
+}
+
+impl<'a> MikkGeometry<'a> {
+    fn vertex(&self, face: usize, vert: usize) -> &MeshVertex {
+        &self.mesh.vertices[self.mesh.indices[face * 3 + vert] as usize]
+    }
+}
+
+impl<'a> Geometry for MikkGeometry<'a> {
+    fn num_faces(&self) -> usize {
+        self.mesh.indices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertex(face, vert).position
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertex(face, vert).normal
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.vertex(face, vert).uv
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.tangents[face * 3 + vert] = Some(tangent);
+    }
+}
+
+/// Makes an arbitrary unit vector perpendicular to `normal`.
+fn perpendicular(normal: Vec3<f32>) -> Vec3<f32> {
+    let reference = if normal.x.abs() < 0.9 {
+        Vec3::unit_x()
+    } else {
+        Vec3::unit_y()
+    };
+    normal
+        .cross(reference)
+        .try_normalized()
+        .unwrap_or_else(Vec3::unit_x)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Error, IndexedMesh, Parser};
+
+    use std::io::Cursor;
+
+    fn mesh(source: &str) -> IndexedMesh {
+        let mut parser = Parser::new(|_, _: &()| Ok(Cursor::new(vec![])));
+        let obj = parser.parse(source.as_bytes(), ()).unwrap();
+        obj.objects()[0].to_indexed_mesh().unwrap()
+    }
+
+    /// Two triangles sharing the edge 1-3, with UVs mirrored along it.
+    const MIRRORED: &str = "\
+v 0 0 0
+v 1 0 0
+v 0 1 0
+v -1 0 0
+vt 0 0
+vt 1 0
+vt 0 1
+vn 0 0 1
+f 1/1/1 2/2/1 3/3/1
+f 1/1/1 3/3/1 4/2/1
+";
+
+    #[test]
+    fn tangents_follow_u_direction() {
+        let source = "v 0 0 0\nv 2 0 0\nv 2 1 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\n\
+                      vn 0 0 1\nf 1/1/1 2/2/1 3/3/1 4/4/1\n";
+        let mesh = mesh(source);
+        let with_tangents = mesh.generate_tangents().unwrap();
+        assert_eq!(with_tangents.vertices(), mesh.vertices());
+        assert_eq!(with_tangents.indices(), mesh.indices());
+        for tangent in with_tangents.tangents().unwrap() {
+            assert_eq!(*tangent, [1.0, 0.0, 0.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn vertices_on_mirror_seam_are_split() {
+        let mesh = mesh(MIRRORED);
+        assert_eq!(mesh.vertices().len(), 4);
+        assert_eq!(mesh.tangents(), None);
+
+        let with_tangents = mesh.generate_tangents().unwrap();
+        let tangents = with_tangents.tangents().unwrap();
+        assert_eq!(with_tangents.vertices().len(), 6);
+        assert_eq!(tangents.len(), 6);
+        assert_eq!(with_tangents.draw_ranges(), mesh.draw_ranges());
+
+        let signs: Vec<_> = with_tangents
+            .indices()
+            .iter()
+            .map(|&i| tangents[i as usize][3])
+            .collect();
+        assert_eq!(signs, [1.0, 1.0, 1.0, -1.0, -1.0, -1.0]);
+        assert_eq!(
+            tangents[with_tangents.indices()[5] as usize],
+            [-1.0, 0.0, 0.0, -1.0]
+        );
+    }
+
+    #[test]
+    fn uvs_and_normals_are_required() {
+        let error = mesh("v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 3//1\n")
+            .generate_tangents()
+            .unwrap_err();
+        assert!(matches!(error, Error::MissingTextureUv));
+
+        let error = mesh("v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nf 1/1 2/1 3/1\n")
+            .generate_tangents()
+            .unwrap_err();
+        assert!(matches!(error, Error::MissingNormal));
+    }
+}
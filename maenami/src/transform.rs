@@ -0,0 +1,507 @@
+use crate::{
+    obj::{FaceList, Group, Object},
+    WavefrontObj,
+};
+
+use std::{collections::HashSet, sync::Arc};
+
+use vek::{Aabb, Mat4, Vec2, Vec3};
+
+/// Represents a coordinate axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Axis {
+    /// X axis
+    X,
+
+    /// Y axis
+    Y,
+
+    /// Z axis
+    Z,
+}
+
+impl Axis {
+    fn index(self) -> usize {
+        match self {
+            Axis::X => 0,
+            Axis::Y => 1,
+            Axis::Z => 2,
+        }
+    }
+}
+
+impl WavefrontObj {
+    /// The axis-aligned bounds of vertices referenced by faces.
+    /// Returns `None` if there are no faces.
+    pub fn bounds(&self) -> Option<Aabb<f32>> {
+        bounds(&self.group_refs())
+    }
+
+    /// The mean position of distinct vertices referenced by faces.
+    /// Returns `None` if there are no faces.
+    pub fn centroid(&self) -> Option<Vec3<f32>> {
+        centroid(&self.group_refs())
+    }
+
+    /// Applies the transformation to all objects.
+    /// See `Group::transform` for details.
+    pub fn transform(&self, matrix: Mat4<f32>) -> WavefrontObj {
+        self.map_groups(|groups| transform(groups, matrix))
+    }
+
+    /// Swaps two axes of all objects.
+    /// See `Group::swap_axes` for details.
+    pub fn swap_axes(&self, a: Axis, b: Axis) -> WavefrontObj {
+        self.transform(swap_matrix(a, b))
+    }
+
+    /// Negates an axis of all objects.
+    /// See `Group::flip_axis` for details.
+    pub fn flip_axis(&self, axis: Axis) -> WavefrontObj {
+        self.transform(flip_matrix(axis))
+    }
+
+    /// Reverses the winding order of all faces.
+    pub fn reverse_winding(&self) -> WavefrontObj {
+        self.map_groups(reverse_winding)
+    }
+
+    /// Moves the center of bounds to the origin, and scales all objects
+    /// uniformly so that the longest side of bounds becomes 1.
+    pub fn normalize_scale(&self) -> WavefrontObj {
+        self.transform(normalize_matrix(self.bounds()))
+    }
+
+    /// Flips V of texture UVs (`v` into `1 - v`) in all objects.
+    pub fn flip_v(&self) -> WavefrontObj {
+        self.map_groups(flip_v)
+    }
+
+    fn group_refs(&self) -> Vec<&Group> {
+        self.objects.iter().flat_map(|o| o.groups.iter()).collect()
+    }
+
+    fn map_groups(&self, f: impl FnOnce(&[&Group]) -> Vec<Group>) -> WavefrontObj {
+        let mut mapped = f(&self.group_refs()).into_iter();
+        let objects = self
+            .objects
+            .iter()
+            .map(|o| Object {
+                name: o.name.clone(),
+                groups: mapped.by_ref().take(o.groups.len()).collect(),
+            })
+            .collect();
+
+        WavefrontObj {
+            objects,
+            materials: self.materials.clone(),
+            parameter_vertices: self.parameter_vertices.clone(),
+            diagnostics: self.diagnostics.clone(),
+        }
+    }
+}
+
+impl Object {
+    /// The axis-aligned bounds of vertices referenced by faces.
+    /// Returns `None` if there are no faces.
+    pub fn bounds(&self) -> Option<Aabb<f32>> {
+        bounds(&self.group_refs())
+    }
+
+    /// The mean position of distinct vertices referenced by faces.
+    /// Returns `None` if there are no faces.
+    pub fn centroid(&self) -> Option<Vec3<f32>> {
+        centroid(&self.group_refs())
+    }
+
+    /// Applies the transformation to all groups.
+    /// See `Group::transform` for details.
+    pub fn transform(&self, matrix: Mat4<f32>) -> Object {
+        self.map_groups(|groups| transform(groups, matrix))
+    }
+
+    /// Swaps two axes of all groups.
+    /// See `Group::swap_axes` for details.
+    pub fn swap_axes(&self, a: Axis, b: Axis) -> Object {
+        self.transform(swap_matrix(a, b))
+    }
+
+    /// Negates an axis of all groups.
+    /// See `Group::flip_axis` for details.
+    pub fn flip_axis(&self, axis: Axis) -> Object {
+        self.transform(flip_matrix(axis))
+    }
+
+    /// Reverses the winding order of all faces.
+    pub fn reverse_winding(&self) -> Object {
+        self.map_groups(reverse_winding)
+    }
+
+    /// Moves the center of bounds to the origin, and scales all groups
+    /// uniformly so that the longest side of bounds becomes 1.
+    pub fn normalize_scale(&self) -> Object {
+        self.transform(normalize_matrix(self.bounds()))
+    }
+
+    /// Flips V of texture UVs (`v` into `1 - v`) in all groups.
+    pub fn flip_v(&self) -> Object {
+        self.map_groups(flip_v)
+    }
+
+    fn group_refs(&self) -> Vec<&Group> {
+        self.groups.iter().collect()
+    }
+
+    fn map_groups(&self, f: impl FnOnce(&[&Group]) -> Vec<Group>) -> Object {
+        Object {
+            name: self.name.clone(),
+            groups: f(&self.group_refs()).into_boxed_slice(),
+        }
+    }
+}
+
+impl Group {
+    /// The axis-aligned bounds of vertices referenced by faces.
+    /// Returns `None` if there are no faces.
+    pub fn bounds(&self) -> Option<Aabb<f32>> {
+        bounds(&[self])
+    }
+
+    /// The mean position of distinct vertices referenced by faces.
+    /// Returns `None` if there are no faces.
+    pub fn centroid(&self) -> Option<Vec3<f32>> {
+        centroid(&[self])
+    }
+
+    /// Applies the transformation.
+    /// Normals are transformed with the inverse transpose of `matrix` and normalized.
+    /// If `matrix` mirrors the space (negative determinant), the winding order
+    /// of faces is reversed so that front faces stay front.
+    pub fn transform(&self, matrix: Mat4<f32>) -> Group {
+        self.map_group(|groups| transform(groups, matrix))
+    }
+
+    /// Swaps two axes, for example `Y` and `Z` to make Y-up data Z-up.
+    /// This is a reflection and changes the handedness, so the winding order is reversed;
+    /// right-handed Y-up becomes left-handed Z-up. To keep the handedness,
+    /// also negate one of the swapped axes with `flip_axis`.
+    pub fn swap_axes(&self, a: Axis, b: Axis) -> Group {
+        self.transform(swap_matrix(a, b))
+    }
+
+    /// Negates an axis, for example `Z` to convert right-handed into left-handed.
+    /// This changes the handedness, so the winding order is reversed.
+    pub fn flip_axis(&self, axis: Axis) -> Group {
+        self.transform(flip_matrix(axis))
+    }
+
+    /// Reverses the winding order of all faces.
+    pub fn reverse_winding(&self) -> Group {
+        self.map_group(reverse_winding)
+    }
+
+    /// Moves the center of bounds to the origin, and scales uniformly
+    /// so that the longest side of bounds becomes 1.
+    pub fn normalize_scale(&self) -> Group {
+        self.transform(normalize_matrix(self.bounds()))
+    }
+
+    /// Flips V of texture UVs (`v` into `1 - v`).
+    pub fn flip_v(&self) -> Group {
+        self.map_group(flip_v)
+    }
+
+    fn map_group(&self, f: impl FnOnce(&[&Group]) -> Vec<Group>) -> Group {
+        f(&[self]).pop().expect("Should return one group")
+    }
+}
+
+/// Calculates the bounds of vertices referenced in groups.
+fn bounds(groups: &[&Group]) -> Option<Aabb<f32>> {
+    let mut positions = referenced_positions(groups);
+    let mut bounds = Aabb::new_empty(positions.next()?);
+    for position in positions {
+        bounds.expand_to_contain_point(position);
+    }
+    Some(bounds)
+}
+
+/// Calculates the centroid of distinct vertices referenced in groups.
+fn centroid(groups: &[&Group]) -> Option<Vec3<f32>> {
+    let mut visited = HashSet::new();
+    let mut sum = Vec3::<f64>::zero();
+    for group in groups {
+        let pool = group.vertices.as_ptr();
+        for (face, _, _) in group.face_index_pairs.iter() {
+            for pair in face.iter() {
+                if visited.insert((pool, pair.0)) {
+                    sum += group.vertices[pair.0].as_();
+                }
+            }
+        }
+    }
+
+    match visited.len() {
+        0 => None,
+        count => Some((sum / count as f64).as_()),
+    }
+}
+
+/// Iterates positions of vertices referenced in groups.
+/// Vertices may appear multiple times.
+fn referenced_positions<'a>(groups: &'a [&'a Group]) -> impl Iterator<Item = Vec3<f32>> + 'a {
+    groups.iter().flat_map(|g| {
+        g.face_index_pairs
+            .iter()
+            .flat_map(move |(face, _, _)| face.iter().map(move |p| g.vertices[p.0]))
+    })
+}
+
+/// Transforms vertex data of groups.
+fn transform(groups: &[&Group], matrix: Mat4<f32>) -> Vec<Group> {
+    let normal_matrix = matrix.inverted().transposed();
+    let mirrored = matrix.determinant() < 0.0;
+
+    let mut vertices = PoolMapper::default();
+    let mut normals = PoolMapper::default();
+    groups
+        .iter()
+        .map(|group| {
+            let face_index_pairs = if mirrored {
+                reversed_faces(&group.face_index_pairs)
+            } else {
+                group.face_index_pairs.clone()
+            };
+            Group {
+                name: group.name.clone(),
+                names: group.names.clone(),
+                vertices: vertices.map(&group.vertices, |v| matrix.mul_point(v)),
+                texture_uvs: group.texture_uvs.clone(),
+                normals: normals.map(&group.normals, |n| {
+                    normal_matrix.mul_direction(n).try_normalized().unwrap_or(n)
+                }),
+                colors: group.colors.clone(),
+                vertex_ws: group.vertex_ws.clone(),
+                texture_ws: group.texture_ws.clone(),
+                face_index_pairs,
+                lines: group.lines.clone(),
+                points: group.points.clone(),
+            }
+        })
+        .collect()
+}
+
+/// Reverses the winding order of faces in groups.
+fn reverse_winding(groups: &[&Group]) -> Vec<Group> {
+    groups
+        .iter()
+        .map(|group| Group {
+            face_index_pairs: reversed_faces(&group.face_index_pairs),
+            ..(*group).clone()
+        })
+        .collect()
+}
+
+/// Flips V of texture UVs in groups.
+fn flip_v(groups: &[&Group]) -> Vec<Group> {
+    let mut uvs = PoolMapper::default();
+    groups
+        .iter()
+        .map(|group| Group {
+            texture_uvs: uvs.map(&group.texture_uvs, |uv| Vec2::new(uv.x, 1.0 - uv.y)),
+            ..(*group).clone()
+        })
+        .collect()
+}
+
+/// Reverses vertex order in each face, keeping the first vertex.
+fn reversed_faces(faces: &FaceList) -> FaceList {
+    let mut reversed = FaceList::default();
+    let mut buffer = vec![];
+    for (face, material, smoothing) in faces {
+        buffer.clear();
+        buffer.extend_from_slice(face);
+        if let Some((_, rest)) = buffer.split_first_mut() {
+            rest.reverse();
+        }
+        reversed.push(&buffer, material, smoothing);
+    }
+    reversed
+}
+
+/// Makes the matrix swapping two axes.
+fn swap_matrix(a: Axis, b: Axis) -> Mat4<f32> {
+    let mut matrix = Mat4::identity();
+    if a != b {
+        let (a, b) = (a.index(), b.index());
+        matrix[(a, a)] = 0.0;
+        matrix[(b, b)] = 0.0;
+        matrix[(a, b)] = 1.0;
+        matrix[(b, a)] = 1.0;
+    }
+    matrix
+}
+
+/// Makes the matrix negating an axis.
+fn flip_matrix(axis: Axis) -> Mat4<f32> {
+    let mut scale = Vec3::one();
+    scale[axis.index()] = -1.0;
+    Mat4::scaling_3d(scale)
+}
+
+/// Makes the matrix fitting the bounds into unit size at the origin.
+fn normalize_matrix(bounds: Option<Aabb<f32>>) -> Mat4<f32> {
+    let bounds = match bounds {
+        Some(b) => b,
+        None => return Mat4::identity(),
+    };
+    let size = bounds.size();
+    let longest = size.w.max(size.h).max(size.d);
+    let scale = if longest > 0.0 { 1.0 / longest } else { 1.0 };
+    let translation: Mat4<f32> = Mat4::translation_3d(-bounds.center());
+    Mat4::<f32>::scaling_3d(Vec3::broadcast(scale)) * translation
+}
+
+/// Maps shared pools, keeping pools shared after mapping.
+/// Source pools are identified by their addresses.
+struct PoolMapper<T> {
+    mapped: Vec<(*const T, Arc<[T]>)>,
+}
+
+impl<T> Default for PoolMapper<T> {
+    fn default() -> Self {
+        PoolMapper { mapped: vec![] }
+    }
+}
+
+impl<T: Copy> PoolMapper<T> {
+    fn map(&mut self, pool: &Arc<[T]>, f: impl Fn(T) -> T) -> Arc<[T]> {
+        let address = pool.as_ptr();
+        if let Some((_, mapped)) = self.mapped.iter().find(|(a, _)| *a == address) {
+            return mapped.clone();
+        }
+
+        let mapped: Arc<[T]> = pool.iter().map(|&v| f(v)).collect();
+        self.mapped.push((address, mapped.clone()));
+        mapped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Axis;
+    use crate::{Group, Parser, WavefrontObj};
+
+    use std::io::Cursor;
+
+    use vek::{Mat4, Vec2, Vec3};
+
+    fn parse(source: &str) -> WavefrontObj {
+        let mut parser = Parser::new(|_, _: &()| Ok(Cursor::new(vec![])));
+        parser.parse(source.as_bytes(), ()).unwrap()
+    }
+
+    /// A triangle facing +Z, and an unreferenced vertex.
+    const TRIANGLE: &str = "\
+v 0 0 0
+v 2 0 0
+v 0 4 0
+v 10 10 10
+vt 0 0.25
+vn 0 0 1
+g first
+f 1/1/1 2/1/1 3/1/1
+g second
+f 1/1/1 3/1/1 2/1/1
+";
+
+    fn first(obj: &WavefrontObj) -> &Group {
+        &obj.objects()[0].groups()[0]
+    }
+
+    /// The normal of the first face from its winding.
+    fn face_normal(group: &Group) -> Vec3<f32> {
+        let (face, _, _) = group.face_index_pairs().get(0).unwrap();
+        let [a, b, c] = [0, 1, 2].map(|i| group.vertices()[face[i].0]);
+        (b - a).cross(c - a).normalized()
+    }
+
+    #[test]
+    fn bounds_and_centroid_ignore_unreferenced_vertices() {
+        let obj = parse(TRIANGLE);
+        let bounds = obj.bounds().unwrap();
+        assert_eq!(bounds.min, Vec3::zero());
+        assert_eq!(bounds.max, Vec3::new(2.0, 4.0, 0.0));
+        assert_eq!(obj.centroid(), Some(Vec3::new(2.0 / 3.0, 4.0 / 3.0, 0.0)));
+        assert_eq!(parse("v 0 0 0\n").bounds(), None);
+    }
+
+    #[test]
+    fn transforms_normals_with_inverse_transpose() {
+        let matrix = Mat4::<f32>::scaling_3d(Vec3::new(1.0, 1.0, 3.0)) * Mat4::rotation_y(0.5);
+        let obj = parse(TRIANGLE).transform(matrix);
+        let group = first(&obj);
+        assert_eq!(
+            group.vertices()[1],
+            matrix.mul_point(Vec3::new(2.0, 0.0, 0.0))
+        );
+
+        let normal = group.normals()[0];
+        assert!((normal.magnitude() - 1.0).abs() < 1e-6);
+        assert!((face_normal(group) - normal).magnitude() < 1e-6);
+    }
+
+    #[test]
+    fn mirroring_reverses_winding() {
+        let obj = parse(TRIANGLE);
+        for mirrored in [obj.flip_axis(Axis::Z), obj.swap_axes(Axis::Y, Axis::Z)] {
+            let group = first(&mirrored);
+            assert_eq!(face_normal(group), group.normals()[0]);
+            let (face, _, _) = group.face_index_pairs().get(0).unwrap();
+            assert_eq!(face.iter().map(|p| p.0).collect::<Vec<_>>(), [0, 2, 1]);
+        }
+
+        // Two reflections keep the handedness
+        let rotated = obj.swap_axes(Axis::Y, Axis::Z).flip_axis(Axis::Y);
+        let group = first(&rotated);
+        assert_eq!(group.normals()[0], -Vec3::unit_y());
+        assert_eq!(face_normal(group), -Vec3::unit_y());
+        let (face, _, _) = group.face_index_pairs().get(0).unwrap();
+        assert_eq!(face.iter().map(|p| p.0).collect::<Vec<_>>(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn reverses_winding_keeping_first_vertex() {
+        let obj = parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n").reverse_winding();
+        let (face, _, _) = first(&obj).face_index_pairs().get(0).unwrap();
+        assert_eq!(face.iter().map(|p| p.0).collect::<Vec<_>>(), [0, 3, 2, 1]);
+    }
+
+    #[test]
+    fn normalizes_into_unit_size() {
+        let obj = parse(TRIANGLE).normalize_scale();
+        let bounds = obj.bounds().unwrap();
+        assert_eq!(bounds.min, Vec3::new(-0.25, -0.5, 0.0));
+        assert_eq!(bounds.max, Vec3::new(0.25, 0.5, 0.0));
+    }
+
+    #[test]
+    fn flips_v() {
+        let obj = parse(TRIANGLE).flip_v();
+        assert_eq!(first(&obj).texture_uvs(), [Vec2::new(0.0, 0.75)]);
+    }
+
+    #[test]
+    fn keeps_pools_shared() {
+        let obj = parse(TRIANGLE)
+            .transform(Mat4::<f32>::translation_3d(Vec3::unit_x()))
+            .flip_v();
+        let groups = obj.objects()[0].groups();
+        assert!(std::ptr::eq(groups[0].vertices(), groups[1].vertices()));
+        assert!(std::ptr::eq(groups[0].normals(), groups[1].normals()));
+        assert!(std::ptr::eq(
+            groups[0].texture_uvs(),
+            groups[1].texture_uvs()
+        ));
+    }
+}
@@ -0,0 +1,262 @@
+use crate::{
+    obj::{FaceList, Group, Object},
+    WavefrontObj,
+};
+
+use vek::{Vec2, Vec3};
+
+impl WavefrontObj {
+    /// Triangulates all faces in all objects.
+    /// Materials are kept as they are.
+    pub fn triangulate(&self) -> WavefrontObj {
+        WavefrontObj {
+            objects: self.objects.iter().map(|o| o.triangulate()).collect(),
+            materials: self.materials.clone(),
+            parameter_vertices: self.parameter_vertices.clone(),
+            diagnostics: self.diagnostics.clone(),
+        }
+    }
+}
+
+impl Object {
+    /// Triangulates all faces in all groups.
+    pub fn triangulate(&self) -> Object {
+        Object {
+            name: self.name.clone(),
+            groups: self.groups.iter().map(|g| g.triangulate()).collect(),
+        }
+    }
+}
+
+impl Group {
+    /// Triangulates all faces in this group.
+    /// Convex faces are split into fans, and concave faces are split with
+    /// ear clipping on the plane perpendicular to the face normal.
+    /// Each corner keeps its UV/normal indices, and each triangle keeps
+    /// the material of the original face. Faces with less than 3 vertices are removed.
+    pub fn triangulate(&self) -> Group {
+        let mut face_index_pairs = FaceList::default();
+        for (face, material, smoothing) in self.face_index_pairs.iter() {
+            let positions: Vec<_> = face.iter().map(|p| self.vertices[p.0]).collect();
+            for [a, b, c] in triangulate_polygon(&positions) {
+                face_index_pairs.push(&[face[a], face[b], face[c]], material, smoothing);
+            }
+        }
+
+        Group {
+            name: self.name.clone(),
+            names: self.names.clone(),
+            vertices: self.vertices.clone(),
+            texture_uvs: self.texture_uvs.clone(),
+            normals: self.normals.clone(),
+            colors: self.colors.clone(),
+            vertex_ws: self.vertex_ws.clone(),
+            texture_ws: self.texture_ws.clone(),
+            face_index_pairs,
+            lines: self.lines.clone(),
+            points: self.points.clone(),
+        }
+    }
+
+    /// Checks whether all faces in this group are triangles.
+    pub fn is_triangulated(&self) -> bool {
+        self.face_index_pairs
+            .iter()
+            .all(|(face, _, _)| face.len() == 3)
+    }
+}
+
+/// Calculates the (not normalized) normal of polygon with Newell's method.
+/// The length equals twice of the area of polygon.
+pub(crate) fn polygon_normal(positions: &[Vec3<f32>]) -> Vec3<f32> {
+    let mut normal = Vec3::zero();
+    for (i, current) in positions.iter().enumerate() {
+        let next = positions[(i + 1) % positions.len()];
+        normal += current.cross(next);
+    }
+    normal
+}
+
+/// Splits a polygon into triangles.
+/// Returns the indices of corners in `positions`, in the same winding order.
+pub(crate) fn triangulate_polygon(positions: &[Vec3<f32>]) -> Vec<[usize; 3]> {
+    match positions.len() {
+        0..=2 => return vec![],
+        3 => return vec![[0, 1, 2]],
+        _ => (),
+    }
+
+    let normal = polygon_normal(positions);
+    if normal.magnitude_squared() <= f32::EPSILON || is_convex(positions, normal) {
+        return triangulate_fan(positions.len());
+    }
+
+    let projected = project_to_plane(positions, normal.normalized());
+    clip_ears(&projected)
+}
+
+/// Splits a convex polygon into fan.
+fn triangulate_fan(count: usize) -> Vec<[usize; 3]> {
+    (1..(count - 1)).map(|i| [0, i, i + 1]).collect()
+}
+
+/// Checks whether all corners turn in the same direction as `normal`.
+fn is_convex(positions: &[Vec3<f32>], normal: Vec3<f32>) -> bool {
+    let count = positions.len();
+    (0..count).all(|i| {
+        let previous = positions[(i + count - 1) % count];
+        let current = positions[i];
+        let next = positions[(i + 1) % count];
+        (current - previous).cross(next - current).dot(normal) >= 0.0
+    })
+}
+
+/// Projects positions onto the plane perpendicular to `normal`.
+/// The polygon becomes counterclockwise in the result.
+fn project_to_plane(positions: &[Vec3<f32>], normal: Vec3<f32>) -> Vec<Vec2<f32>> {
+    let reference = if normal.x.abs() < 0.9 {
+        Vec3::unit_x()
+    } else {
+        Vec3::unit_y()
+    };
+    let u = normal.cross(reference).normalized();
+    let v = normal.cross(u);
+    positions
+        .iter()
+        .map(|p| Vec2::new(p.dot(u), p.dot(v)))
+        .collect()
+}
+
+/// Triangulates a counterclockwise simple polygon with ear clipping.
+fn clip_ears(points: &[Vec2<f32>]) -> Vec<[usize; 3]> {
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let ear = (0..count).find(|&i| {
+            let a = remaining[(i + count - 1) % count];
+            let b = remaining[i];
+            let c = remaining[(i + 1) % count];
+            if cross_2d(points[a], points[b], points[c]) <= 0.0 {
+                return false;
+            }
+            remaining
+                .iter()
+                .filter(|&&j| j != a && j != b && j != c)
+                .all(|&j| !contains_point(points[a], points[b], points[c], points[j]))
+        });
+
+        match ear {
+            Some(i) => {
+                let a = remaining[(i + count - 1) % count];
+                let c = remaining[(i + 1) % count];
+                triangles.push([a, remaining[i], c]);
+                remaining.remove(i);
+            }
+            None => {
+                // Self-intersecting or degenerate; give up and make a fan of the rest
+                for i in 1..(count - 1) {
+                    triangles.push([remaining[0], remaining[i], remaining[i + 1]]);
+                }
+                return triangles;
+            }
+        }
+    }
+
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
+
+/// Calculates the z component of `(b - a) x (c - b)`.
+fn cross_2d(a: Vec2<f32>, b: Vec2<f32>, c: Vec2<f32>) -> f32 {
+    let ab = b - a;
+    let bc = c - b;
+    ab.x * bc.y - ab.y * bc.x
+}
+
+/// Checks whether `p` is inside of (or on the edge of) counterclockwise triangle `abc`.
+fn contains_point(a: Vec2<f32>, b: Vec2<f32>, c: Vec2<f32>, p: Vec2<f32>) -> bool {
+    cross_2d(a, b, p) >= 0.0 && cross_2d(b, c, p) >= 0.0 && cross_2d(c, a, p) >= 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FaceIndexPair, Parser};
+
+    use std::io::Cursor;
+
+    /// Checks that triangles cover the polygon with the same winding.
+    fn assert_covers(positions: &[Vec3<f32>], triangles: &[[usize; 3]]) {
+        let normal = polygon_normal(positions);
+        assert_eq!(triangles.len(), positions.len() - 2);
+        let mut area = 0.0;
+        for triangle in triangles {
+            let corners = triangle.map(|i| positions[i]);
+            let triangle_normal = polygon_normal(&corners);
+            assert!(triangle_normal.dot(normal) > 0.0, "{:?}", triangle);
+            area += triangle_normal.magnitude();
+        }
+        assert!((area - normal.magnitude()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn convex_polygons_become_fans() {
+        let square = [
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ];
+        assert_eq!(triangulate_polygon(&square), [[0, 1, 2], [0, 2, 3]]);
+        assert!(triangulate_polygon(&square[..2]).is_empty());
+    }
+
+    #[test]
+    fn concave_polygons_are_clipped_on_their_plane() {
+        // L shape on a tilted plane, starting at the reflex corner
+        let shape = [
+            (1.0, 1.0),
+            (2.0, 1.0),
+            (2.0, 2.0),
+            (0.0, 2.0),
+            (0.0, 0.0),
+            (1.0, 0.0),
+        ];
+        let positions: Vec<_> = shape.iter().map(|&(x, y)| Vec3::new(x, y, x + y)).collect();
+        let triangles = triangulate_polygon(&positions);
+        assert_covers(&positions, &triangles);
+
+        let mut reversed = positions.clone();
+        reversed.reverse();
+        assert_covers(&reversed, &triangulate_polygon(&reversed));
+    }
+
+    #[test]
+    fn groups_keep_indices_and_materials() {
+        let source = "\
+mtllib a.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vn 0 0 1
+usemtl a
+f 1/1/1 2/1/1 3/1/1 4/1/1
+f 1 2
+";
+        let mut parser = Parser::new(|_, _: &()| Ok(Cursor::new(b"newmtl a\n")));
+        let obj = parser.parse(source.as_bytes(), ()).unwrap().triangulate();
+        let group = &obj.objects()[0].groups()[0];
+        assert!(group.is_triangulated());
+        assert_eq!(group.face_index_pairs().len(), 2);
+        for (face, material, _) in group.face_index_pairs() {
+            assert_eq!(material, Some(0));
+            assert!(face.iter().all(|p| p.1 == Some(0) && p.2 == Some(0)));
+        }
+        let (face, _, _) = group.face_index_pairs().get(1).unwrap();
+        assert_eq!(face[2], FaceIndexPair(3, Some(0), Some(0)));
+    }
+}
@@ -0,0 +1,590 @@
+use crate::{
+    obj::{all_share_vertex_data, ElementIndices, FaceIndexPair, FaceList, Group, Object},
+    triangulate::polygon_normal,
+    WavefrontObj,
+};
+
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fmt::{Display, Formatter, Result as FmtResult},
+    sync::Arc,
+};
+
+use vek::Vec3;
+
+/// The tolerance of normal length.
+const NORMAL_TOLERANCE: f32 = 1e-3;
+
+/// Represents a problem found in mesh validation.
+/// Vertex, normal and UV indices are 0-based, and refer the shared pools.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ValidationIssue {
+    /// Face with fewer than three vertices.
+    TooFewVertices {
+        object: usize,
+        group: usize,
+        face: usize,
+    },
+
+    /// Face whose area is zero, such as one with collinear or coincident vertices.
+    DegenerateFace {
+        object: usize,
+        group: usize,
+        face: usize,
+    },
+
+    /// Edge used by only one face in the object.
+    BoundaryEdge { object: usize, vertices: [usize; 2] },
+
+    /// Edge shared by more than two faces in the object.
+    NonManifoldEdge {
+        object: usize,
+        vertices: [usize; 2],
+        faces: usize,
+    },
+
+    /// Vertex referenced by no face, line or point.
+    UnusedVertex(usize),
+
+    /// Vertex whose position has NaN or infinity.
+    NonFiniteVertex(usize),
+
+    /// Normal which is not unit length, or has NaN or infinity.
+    InvalidNormal(usize),
+
+    /// Texture UV outside of `[0, 1]`, or with NaN or infinity.
+    UvOutOfRange(usize),
+}
+
+impl Display for ValidationIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            ValidationIssue::TooFewVertices {
+                object,
+                group,
+                face,
+            } => write!(
+                f,
+                "Face {} in object {} group {} has fewer than three vertices",
+                face, object, group
+            ),
+            ValidationIssue::DegenerateFace {
+                object,
+                group,
+                face,
+            } => write!(
+                f,
+                "Face {} in object {} group {} is degenerate",
+                face, object, group
+            ),
+            ValidationIssue::BoundaryEdge {
+                object,
+                vertices: [a, b],
+            } => write!(f, "Edge {}-{} in object {} is boundary", a, b, object),
+            ValidationIssue::NonManifoldEdge {
+                object,
+                vertices: [a, b],
+                faces,
+            } => write!(
+                f,
+                "Edge {}-{} in object {} is shared by {} faces",
+                a, b, object, faces
+            ),
+            ValidationIssue::UnusedVertex(i) => write!(f, "Vertex {} is unused", i),
+            ValidationIssue::NonFiniteVertex(i) => write!(f, "Vertex {} is not finite", i),
+            ValidationIssue::InvalidNormal(i) => write!(f, "Normal {} is not normalized", i),
+            ValidationIssue::UvOutOfRange(i) => write!(f, "Texture UV {} is out of range", i),
+        }
+    }
+}
+
+/// Represents what `WavefrontObj::repair` fixes.
+/// By default, everything except welding is enabled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RepairOptions {
+    renormalize: bool,
+    drop_degenerate: bool,
+    weld_distance: Option<f32>,
+    compact: bool,
+}
+
+impl Default for RepairOptions {
+    fn default() -> RepairOptions {
+        RepairOptions {
+            renormalize: true,
+            drop_degenerate: true,
+            weld_distance: None,
+            compact: true,
+        }
+    }
+}
+
+impl RepairOptions {
+    /// Creates an instance of `RepairOptions` with default options.
+    pub fn new() -> RepairOptions {
+        RepairOptions::default()
+    }
+
+    /// Normalizes normals.
+    /// References to normals which can't be normalized (zero, NaN or infinity) are removed.
+    pub fn renormalize(mut self, enabled: bool) -> RepairOptions {
+        self.renormalize = enabled;
+        self
+    }
+
+    /// Drops faces with fewer than three distinct vertices or zero area,
+    /// and removes repeated vertices in faces and lines.
+    pub fn drop_degenerate(mut self, enabled: bool) -> RepairOptions {
+        self.drop_degenerate = enabled;
+        self
+    }
+
+    /// Welds vertices within the distance into the first one.
+    /// Zero distance welds only vertices at identical positions.
+    pub fn weld(mut self, distance: Option<f32>) -> RepairOptions {
+        self.weld_distance = distance;
+        self
+    }
+
+    /// Removes vertices, UVs and normals which are not referenced.
+    pub fn compact(mut self, enabled: bool) -> RepairOptions {
+        self.compact = enabled;
+        self
+    }
+}
+
+impl WavefrontObj {
+    /// Validates faces and vertex data.
+    /// Groups always share vertex data as parsed, so vertex data are checked once.
+    /// Edges are checked in each object.
+    pub fn validate(&self) -> Box<[ValidationIssue]> {
+        debug_assert!(
+            all_share_vertex_data(self.objects.iter().flat_map(|o| o.groups.iter())),
+            "Groups should share vertex data"
+        );
+        let first = match self.objects.iter().flat_map(|o| o.groups.iter()).next() {
+            Some(g) => g,
+            None => return Box::default(),
+        };
+
+        let mut issues = vec![];
+        let mut used = vec![false; first.vertices.len()];
+        for (object_index, object) in self.objects.iter().enumerate() {
+            let mut edges: HashMap<[usize; 2], usize> = HashMap::new();
+            for (group_index, group) in object.groups.iter().enumerate() {
+                for (face_index, (face, _, _)) in group.face_index_pairs.iter().enumerate() {
+                    face.iter().for_each(|p| used[p.0] = true);
+                    if face.len() < 3 {
+                        issues.push(ValidationIssue::TooFewVertices {
+                            object: object_index,
+                            group: group_index,
+                            face: face_index,
+                        });
+                        continue;
+                    }
+
+                    let positions: Vec<_> = face.iter().map(|p| group.vertices[p.0]).collect();
+                    if is_degenerate(&positions) {
+                        issues.push(ValidationIssue::DegenerateFace {
+                            object: object_index,
+                            group: group_index,
+                            face: face_index,
+                        });
+                    }
+                    for (i, pair) in face.iter().enumerate() {
+                        let next = face[(i + 1) % face.len()].0;
+                        if pair.0 != next {
+                            let edge = [pair.0.min(next), pair.0.max(next)];
+                            *edges.entry(edge).or_default() += 1;
+                        }
+                    }
+                }
+                for (pairs, _) in group.lines.iter().chain(group.points.iter()) {
+                    pairs.iter().for_each(|p| used[p.0] = true);
+                }
+            }
+
+            let mut edges: Vec<_> = edges.into_iter().collect();
+            edges.sort_unstable();
+            for (vertices, faces) in edges {
+                match faces {
+                    1 => issues.push(ValidationIssue::BoundaryEdge {
+                        object: object_index,
+                        vertices,
+                    }),
+                    2 => (),
+                    _ => issues.push(ValidationIssue::NonManifoldEdge {
+                        object: object_index,
+                        vertices,
+                        faces,
+                    }),
+                }
+            }
+        }
+
+        for (i, vertex) in first.vertices.iter().enumerate() {
+            if !used[i] {
+                issues.push(ValidationIssue::UnusedVertex(i));
+            }
+            if !vertex.map(f32::is_finite).reduce_and() {
+                issues.push(ValidationIssue::NonFiniteVertex(i));
+            }
+        }
+        for (i, normal) in first.normals.iter().enumerate() {
+            let error = (normal.magnitude() - 1.0).abs();
+            if error.partial_cmp(&NORMAL_TOLERANCE) != Some(Ordering::Less) {
+                issues.push(ValidationIssue::InvalidNormal(i));
+            }
+        }
+        for (i, uv) in first.texture_uvs.iter().enumerate() {
+            if !uv.map(|c| (0.0..=1.0).contains(&c)).reduce_and() {
+                issues.push(ValidationIssue::UvOutOfRange(i));
+            }
+        }
+
+        issues.into_boxed_slice()
+    }
+
+    /// Fixes problems found by `validate`.
+    /// Groups always share vertex data as parsed, and the result groups share
+    /// the repaired vertex data. Groups and objects which become empty are removed.
+    pub fn repair(&self, options: RepairOptions) -> WavefrontObj {
+        debug_assert!(
+            all_share_vertex_data(self.objects.iter().flat_map(|o| o.groups.iter())),
+            "Groups should share vertex data"
+        );
+        let first = match self.objects.iter().flat_map(|o| o.groups.iter()).next() {
+            Some(g) => g,
+            None => return self.clone(),
+        };
+
+        let mut normals = first.normals.to_vec();
+        let mut valid_normals = vec![true; normals.len()];
+        if options.renormalize {
+            for (normal, valid) in normals.iter_mut().zip(valid_normals.iter_mut()) {
+                match normal.try_normalized() {
+                    Some(n) if n.map(f32::is_finite).reduce_and() => *normal = n,
+                    _ => *valid = false,
+                }
+            }
+        }
+
+        let vertex_map = match options.weld_distance {
+            Some(distance) => weld(&first.vertices, distance),
+            None => (0..first.vertices.len()).collect(),
+        };
+        let map_pair = |&FaceIndexPair(v, t, n): &FaceIndexPair| {
+            FaceIndexPair(vertex_map[v], t, n.filter(|&n| valid_normals[n]))
+        };
+
+        let mut objects = vec![];
+        for object in self.objects.iter() {
+            let mut groups = vec![];
+            for group in object.groups.iter() {
+                let mut faces = FaceList::default();
+                for (face, material, smoothing) in group.face_index_pairs.iter() {
+                    let mut face: Vec<_> = face.iter().map(map_pair).collect();
+                    if options.drop_degenerate {
+                        remove_repeated(&mut face, true);
+                        let positions: Vec<_> = face.iter().map(|p| first.vertices[p.0]).collect();
+                        if face.len() < 3 || is_degenerate(&positions) {
+                            continue;
+                        }
+                    }
+                    faces.push(&face, material, smoothing);
+                }
+
+                let mut lines: Vec<ElementIndices> = vec![];
+                for (line, material) in group.lines.iter() {
+                    let mut line: Vec<_> = line.iter().map(map_pair).collect();
+                    if options.drop_degenerate {
+                        remove_repeated(&mut line, false);
+                        if line.len() < 2 {
+                            continue;
+                        }
+                    }
+                    lines.push((line.into_boxed_slice(), *material));
+                }
+
+                let points: Vec<ElementIndices> = group
+                    .points
+                    .iter()
+                    .map(|(points, material)| (points.iter().map(map_pair).collect(), *material))
+                    .collect();
+
+                if !faces.is_empty() || !lines.is_empty() || !points.is_empty() {
+                    groups.push(Group {
+                        name: group.name.clone(),
+                        names: group.names.clone(),
+                        vertices: Default::default(),
+                        texture_uvs: Default::default(),
+                        normals: Default::default(),
+                        colors: None,
+                        vertex_ws: None,
+                        texture_ws: None,
+                        face_index_pairs: faces,
+                        lines: lines.into_boxed_slice(),
+                        points: points.into_boxed_slice(),
+                    });
+                }
+            }
+
+            if !groups.is_empty() {
+                objects.push(Object {
+                    name: object.name.clone(),
+                    groups: groups.into_boxed_slice(),
+                });
+            }
+        }
+
+        let mut vertices = first.vertices.to_vec();
+        let mut uvs = first.texture_uvs.to_vec();
+        let mut colors = first.colors.as_ref().map(|c| c.to_vec());
+        let mut vertex_ws = first.vertex_ws.as_ref().map(|w| w.to_vec());
+        let mut texture_ws = first.texture_ws.as_ref().map(|w| w.to_vec());
+        if options.compact {
+            let mut used_vertices = vec![false; vertices.len()];
+            let mut used_uvs = vec![false; uvs.len()];
+            let mut used_normals = vec![false; normals.len()];
+            for pair in all_pairs(&objects) {
+                used_vertices[pair.0] = true;
+                pair.1.iter().for_each(|&t| used_uvs[t] = true);
+                pair.2.iter().for_each(|&n| used_normals[n] = true);
+            }
+
+            let vertex_indices = compact(&mut vertices, &used_vertices);
+            let uv_indices = compact(&mut uvs, &used_uvs);
+            let normal_indices = compact(&mut normals, &used_normals);
+            if let Some(colors) = &mut colors {
+                compact(colors, &used_vertices);
+            }
+            if let Some(vertex_ws) = &mut vertex_ws {
+                compact(vertex_ws, &used_vertices);
+            }
+            if let Some(texture_ws) = &mut texture_ws {
+                compact(texture_ws, &used_uvs);
+            }
+            for pair in all_pairs_mut(&mut objects) {
+                pair.0 = vertex_indices[pair.0];
+                pair.1 = pair.1.map(|t| uv_indices[t]);
+                pair.2 = pair.2.map(|n| normal_indices[n]);
+            }
+        }
+
+        let vertices: Arc<[_]> = vertices.into();
+        let uvs: Arc<[_]> = uvs.into();
+        let normals: Arc<[_]> = normals.into();
+        let colors: Option<Arc<[_]>> = colors.map(|c| c.into());
+        let vertex_ws: Option<Arc<[_]>> = vertex_ws.map(|w| w.into());
+        let texture_ws: Option<Arc<[_]>> = texture_ws.map(|w| w.into());
+        for group in objects.iter_mut().flat_map(|o| o.groups.iter_mut()) {
+            group.vertices = vertices.clone();
+            group.texture_uvs = uvs.clone();
+            group.normals = normals.clone();
+            group.colors = colors.clone();
+            group.vertex_ws = vertex_ws.clone();
+            group.texture_ws = texture_ws.clone();
+        }
+
+        WavefrontObj {
+            objects: objects.into_boxed_slice(),
+            materials: self.materials.clone(),
+            parameter_vertices: self.parameter_vertices.clone(),
+            diagnostics: self.diagnostics.clone(),
+        }
+    }
+}
+
+/// Checks whether the polygon has (almost) zero area.
+/// Polygons with NaN are also degenerate.
+fn is_degenerate(positions: &[Vec3<f32>]) -> bool {
+    let area = polygon_normal(positions).magnitude();
+    let longest = (0..positions.len())
+        .map(|i| positions[i].distance_squared(positions[(i + 1) % positions.len()]))
+        .fold(0.0, f32::max);
+    area.partial_cmp(&(f32::EPSILON * longest)) != Some(Ordering::Greater)
+}
+
+/// Removes vertices same as the previous one.
+/// If `closed`, the last vertex is also compared with the first one.
+fn remove_repeated(pairs: &mut Vec<FaceIndexPair>, closed: bool) {
+    pairs.dedup_by_key(|p| p.0);
+    while closed && pairs.len() > 1 && pairs.first().map(|p| p.0) == pairs.last().map(|p| p.0) {
+        pairs.pop();
+    }
+}
+
+/// Maps each vertex to the first vertex within the distance.
+fn weld(vertices: &[Vec3<f32>], distance: f32) -> Vec<usize> {
+    // Vertices are bucketed into cells of the distance, and only neighboring cells are searched
+    let cell_size = if distance > 0.0 { distance } else { 1.0 };
+    let cell = |p: Vec3<f32>| (p / cell_size).map(|c| c.floor() as i64).into_array();
+
+    let mut cells: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+    let mut vertex_map = Vec::with_capacity(vertices.len());
+    for (i, &position) in vertices.iter().enumerate() {
+        let [x, y, z] = cell(position);
+        let found = (-1..=1)
+            .flat_map(|dx| {
+                (-1..=1).flat_map(move |dy| (-1..=1).map(move |dz| [x + dx, y + dy, z + dz]))
+            })
+            .filter_map(|c| cells.get(&c))
+            .flatten()
+            .copied()
+            .find(|&j| vertices[j].distance(position) <= distance.max(0.0));
+
+        match found {
+            Some(j) => vertex_map.push(j),
+            None => {
+                cells.entry([x, y, z]).or_default().push(i);
+                vertex_map.push(i);
+            }
+        }
+    }
+    vertex_map
+}
+
+/// Removes unused elements.
+/// Returns the new indices of elements (unused ones are left as `usize::MAX`).
+fn compact<T: Copy>(pool: &mut Vec<T>, used: &[bool]) -> Vec<usize> {
+    let mut indices = vec![usize::MAX; pool.len()];
+    let mut count = 0;
+    for (i, index) in indices.iter_mut().enumerate() {
+        if used[i] {
+            pool[count] = pool[i];
+            *index = count;
+            count += 1;
+        }
+    }
+    pool.truncate(count);
+    indices
+}
+
+fn all_pairs(objects: &[Object]) -> impl Iterator<Item = &FaceIndexPair> {
+    objects.iter().flat_map(|o| o.groups.iter()).flat_map(|g| {
+        let faces = g.face_index_pairs.index_pairs().iter();
+        let elements = g
+            .lines
+            .iter()
+            .chain(g.points.iter())
+            .flat_map(|(e, _)| e.iter());
+        faces.chain(elements)
+    })
+}
+
+fn all_pairs_mut(objects: &mut [Object]) -> impl Iterator<Item = &mut FaceIndexPair> {
+    objects
+        .iter_mut()
+        .flat_map(|o| o.groups.iter_mut())
+        .flat_map(|g| {
+            let faces = g.face_index_pairs.index_pairs_mut().iter_mut();
+            let elements = g
+                .lines
+                .iter_mut()
+                .chain(g.points.iter_mut())
+                .flat_map(|(e, _)| e.iter_mut());
+            faces.chain(elements)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Parser;
+
+    use std::io::Cursor;
+
+    fn parse(source: &str) -> WavefrontObj {
+        let mut parser = Parser::new(|_, _: &()| Ok(Cursor::new(vec![])));
+        parser.parse(source.as_bytes(), ()).unwrap()
+    }
+
+    const BROKEN: &str = "\
+v 0 0 0
+v 1 0 0
+v 0 1 0
+v 2 2 2
+v 0 0 0
+vt 0.5 1.5
+vn 0 0 2
+vn 0 0 0
+f 1//1 2//2 3//1
+f 1 2 2
+f 1 2
+l 1 1 3
+";
+
+    #[test]
+    fn problems_are_found() {
+        let issues = parse(BROKEN).validate();
+        let expected = [
+            ValidationIssue::DegenerateFace {
+                object: 0,
+                group: 0,
+                face: 1,
+            },
+            ValidationIssue::TooFewVertices {
+                object: 0,
+                group: 0,
+                face: 2,
+            },
+            ValidationIssue::NonManifoldEdge {
+                object: 0,
+                vertices: [0, 1],
+                faces: 3,
+            },
+            ValidationIssue::BoundaryEdge {
+                object: 0,
+                vertices: [0, 2],
+            },
+            ValidationIssue::BoundaryEdge {
+                object: 0,
+                vertices: [1, 2],
+            },
+            ValidationIssue::UnusedVertex(3),
+            ValidationIssue::UnusedVertex(4),
+            ValidationIssue::InvalidNormal(0),
+            ValidationIssue::InvalidNormal(1),
+            ValidationIssue::UvOutOfRange(0),
+        ];
+        assert_eq!(&*issues, expected);
+    }
+
+    #[test]
+    fn repair_fixes_problems() {
+        let repaired = parse(BROKEN).repair(RepairOptions::new());
+        let group = &repaired.objects()[0].groups()[0];
+        assert_eq!(group.face_index_pairs().len(), 1);
+        let (face, _, _) = group.face_index_pairs().get(0).unwrap();
+        assert_eq!(
+            face,
+            [
+                FaceIndexPair(0, None, Some(0)),
+                FaceIndexPair(1, None, None),
+                FaceIndexPair(2, None, Some(0)),
+            ]
+        );
+        assert_eq!(group.line_index_pairs()[0].0.len(), 2);
+        assert_eq!(group.vertices().len(), 3);
+        assert_eq!(group.normals(), [Vec3::unit_z()]);
+        assert!(group.texture_uvs().is_empty());
+    }
+
+    #[test]
+    fn weld_merges_close_vertices() {
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1.001 0 0\nv 1 1 0\nf 1 2 3\nf 4 5 3\n";
+        let repaired = parse(source).repair(RepairOptions::new().weld(Some(0.01)));
+        let group = &repaired.objects()[0].groups()[0];
+        assert_eq!(group.vertices().len(), 4);
+        let (second, _, _) = group.face_index_pairs().get(1).unwrap();
+        assert_eq!(second[0].0, 1);
+
+        let issues = repaired.validate();
+        assert!(!issues
+            .iter()
+            .any(|i| matches!(i, ValidationIssue::UnusedVertex(_))));
+    }
+}
@@ -0,0 +1,394 @@
+use crate::{
+    mtl::{MaterialProperty, TextureChannel, TextureMap},
+    obj::{shares_vertex_data, FaceIndexPair, Group, NULL_MATERIAL},
+    Result, WavefrontObj,
+};
+
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    io::{prelude::*, BufWriter},
+    path::Path,
+};
+
+use vek::Vec3;
+
+impl WavefrontObj {
+    /// Writes objects as OBJ file.
+    /// If `mtllib` is specified, `mtllib` command referring it is written.
+    /// Materials are referred with the names written by `write_mtl`.
+    /// The writer will be wrapped with `BufWriter`, so you don't have to do so.
+    pub fn write_obj(&self, writer: impl Write, mtllib: Option<&Path>) -> Result<()> {
+        let mut writer = BufWriter::new(writer);
+        let material_names = self.unique_material_names();
+        if let Some(path) = mtllib {
+            writeln!(writer, "mtllib {}", path.display())?;
+        }
+        for vp in self.parameter_vertices.iter() {
+            writeln!(writer, "vp {} {} {}", vp.x, vp.y, vp.z)?;
+        }
+
+        // Groups may have different vertex data; write each of them once
+        let mut pools: Vec<(&Group, VertexOffsets)> = vec![];
+        let mut offsets = VertexOffsets::default();
+        for group in self.objects.iter().flat_map(|o| o.groups.iter()) {
+            if pools.iter().any(|(g, _)| shares_vertex_data(g, group)) {
+                continue;
+            }
+
+            for (i, v) in group.vertices.iter().enumerate() {
+                write!(writer, "v {} {} {}", v.x, v.y, v.z)?;
+                if let Some(w) = &group.vertex_ws {
+                    write!(writer, " {}", w[i])?;
+                }
+                if let Some(c) = &group.colors {
+                    write!(writer, " {} {} {}", c[i].r, c[i].g, c[i].b)?;
+                }
+                writeln!(writer)?;
+            }
+            for (i, vt) in group.texture_uvs.iter().enumerate() {
+                match &group.texture_ws {
+                    Some(w) => writeln!(writer, "vt {} {} {}", vt.x, vt.y, w[i])?,
+                    None => writeln!(writer, "vt {} {}", vt.x, vt.y)?,
+                }
+            }
+            for vn in group.normals.iter() {
+                writeln!(writer, "vn {} {} {}", vn.x, vn.y, vn.z)?;
+            }
+            pools.push((group, offsets));
+            offsets.vertices += group.vertices.len();
+            offsets.uvs += group.texture_uvs.len();
+            offsets.normals += group.normals.len();
+        }
+
+        let mut current_material = None;
+        let mut current_smoothing = None;
+        for (object_index, object) in self.objects.iter().enumerate() {
+            match (&object.name, object_index) {
+                (Some(name), _) => writeln!(writer, "o {}", name)?,
+                (None, 0) => (),
+                (None, _) => writeln!(writer, "o")?,
+            }
+
+            for (group_index, group) in object.groups.iter().enumerate() {
+                match (&group.name, group_index) {
+                    (Some(name), _) => writeln!(writer, "g {}", name)?,
+                    (None, 0) => (),
+                    (None, _) => writeln!(writer, "g")?,
+                }
+
+                let offsets = pools
+                    .iter()
+                    .find(|(g, _)| shares_vertex_data(g, group))
+                    .map(|(_, o)| *o)
+                    .expect("All vertex data should be written");
+                for (face, material, smoothing) in group.face_index_pairs.iter() {
+                    write_usemtl(
+                        &mut writer,
+                        &material_names,
+                        material,
+                        &mut current_material,
+                    )?;
+                    if smoothing != current_smoothing {
+                        match smoothing {
+                            Some(s) => writeln!(writer, "s {}", s)?,
+                            None => writeln!(writer, "s off")?,
+                        }
+                        current_smoothing = smoothing;
+                    }
+                    write_element(&mut writer, "f", face, offsets)?;
+                }
+                for (line, material) in group.lines.iter() {
+                    write_usemtl(
+                        &mut writer,
+                        &material_names,
+                        *material,
+                        &mut current_material,
+                    )?;
+                    write_element(&mut writer, "l", line, offsets)?;
+                }
+                for (points, material) in group.points.iter() {
+                    write_usemtl(
+                        &mut writer,
+                        &material_names,
+                        *material,
+                        &mut current_material,
+                    )?;
+                    write_element(&mut writer, "p", points, offsets)?;
+                }
+            }
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes materials as MTL file.
+    /// Properties are written in the order of their names.
+    /// Names defined more than once (e.g. in multiple `mtllib` files) are renamed with
+    /// suffixes like `.2`, so that `usemtl` in `write_obj` refers the same materials.
+    /// The writer will be wrapped with `BufWriter`, so you don't have to do so.
+    pub fn write_mtl(&self, writer: impl Write) -> Result<()> {
+        let mut writer = BufWriter::new(writer);
+        let material_names = self.unique_material_names();
+        for (i, material) in self.materials.iter().enumerate() {
+            if i > 0 {
+                writeln!(writer)?;
+            }
+            writeln!(writer, "newmtl {}", material_names[i])?;
+
+            let mut keys: Vec<_> = material.properties.keys().collect();
+            keys.sort();
+            for key in keys {
+                match (key.as_str(), &material.properties[key]) {
+                    (key, MaterialProperty::Float(v)) => writeln!(writer, "{} {}", key, v)?,
+                    (key, MaterialProperty::Integer(v)) => writeln!(writer, "{} {}", key, v)?,
+                    (key, MaterialProperty::Vector(v)) => {
+                        writeln!(writer, "{} {} {} {}", key, v.x, v.y, v.z)?
+                    }
+                    (key, MaterialProperty::Spectral(path, factor)) => {
+                        writeln!(writer, "{} spectral {} {}", key, path.display(), factor)?
+                    }
+                    (key, MaterialProperty::Xyz(v)) => {
+                        writeln!(writer, "{} xyz {} {} {}", key, v.x, v.y, v.z)?
+                    }
+                    (key, MaterialProperty::Dissolve(v, false)) => {
+                        writeln!(writer, "{} {}", key, v)?
+                    }
+                    (key, MaterialProperty::Dissolve(v, true)) => {
+                        writeln!(writer, "{} -halo {}", key, v)?
+                    }
+                    (key, MaterialProperty::Texture(t)) => {
+                        writeln!(writer, "{}{} {}", key, texture_options(t), t.path.display())?
+                    }
+                }
+            }
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl WavefrontObj {
+    /// Makes material names unique, keeping first ones as they are.
+    fn unique_material_names(&self) -> Vec<Cow<'_, str>> {
+        let mut used = HashSet::new();
+        self.materials
+            .iter()
+            .map(|material| {
+                let mut name = Cow::Borrowed(&*material.name);
+                let mut suffix = 2;
+                while !used.insert(name.clone()) {
+                    name = Cow::Owned(format!("{}.{}", material.name, suffix));
+                    suffix += 1;
+                }
+                name
+            })
+            .collect()
+    }
+}
+
+/// Offsets of vertex data in written OBJ file.
+#[derive(Debug, Clone, Copy, Default)]
+struct VertexOffsets {
+    vertices: usize,
+    uvs: usize,
+    normals: usize,
+}
+
+/// Writes `usemtl` if the material differs from the current one.
+fn write_usemtl(
+    writer: &mut impl Write,
+    material_names: &[Cow<str>],
+    material: Option<usize>,
+    current_material: &mut Option<usize>,
+) -> Result<()> {
+    if material != *current_material {
+        let name = material
+            .and_then(|i| material_names.get(i))
+            .map_or(NULL_MATERIAL, |n| n);
+        writeln!(writer, "usemtl {}", name)?;
+        *current_material = material;
+    }
+    Ok(())
+}
+
+/// Writes `f`, `l` or `p` with 1-based indices.
+fn write_element(
+    writer: &mut impl Write,
+    keyword: &str,
+    pairs: &[FaceIndexPair],
+    offsets: VertexOffsets,
+) -> Result<()> {
+    write!(writer, "{}", keyword)?;
+    for &FaceIndexPair(v, t, n) in pairs {
+        write!(writer, " {}", v + offsets.vertices + 1)?;
+        match (t, n) {
+            (Some(t), Some(n)) => write!(
+                writer,
+                "/{}/{}",
+                t + offsets.uvs + 1,
+                n + offsets.normals + 1
+            )?,
+            (Some(t), None) => write!(writer, "/{}", t + offsets.uvs + 1)?,
+            (None, Some(n)) => write!(writer, "//{}", n + offsets.normals + 1)?,
+            (None, None) => (),
+        }
+    }
+    writeln!(writer)?;
+    Ok(())
+}
+
+/// Formats the options of texture map which differ from the default.
+/// Each option is preceded by a space.
+fn texture_options(texture_map: &TextureMap) -> String {
+    let default = TextureMap::new(Path::new(""));
+    let switch = |v| if v { "on" } else { "off" };
+    let vector = |v: Vec3<f32>| format!("{} {} {}", v.x, v.y, v.z);
+
+    let mut options = String::new();
+    if texture_map.origin != default.origin {
+        options += &format!(" -o {}", vector(texture_map.origin));
+    }
+    if texture_map.scale != default.scale {
+        options += &format!(" -s {}", vector(texture_map.scale));
+    }
+    if texture_map.turbulence != default.turbulence {
+        options += &format!(" -t {}", vector(texture_map.turbulence));
+    }
+    if let Some(bm) = texture_map.bump_multiplier {
+        options += &format!(" -bm {}", bm);
+    }
+    if texture_map.clamp != default.clamp {
+        options += &format!(" -clamp {}", switch(texture_map.clamp));
+    }
+    if texture_map.blend_u != default.blend_u {
+        options += &format!(" -blendu {}", switch(texture_map.blend_u));
+    }
+    if texture_map.blend_v != default.blend_v {
+        options += &format!(" -blendv {}", switch(texture_map.blend_v));
+    }
+    if texture_map.color_correction != default.color_correction {
+        options += &format!(" -cc {}", switch(texture_map.color_correction));
+    }
+    if texture_map.value_range != default.value_range {
+        let (base, gain) = texture_map.value_range;
+        options += &format!(" -mm {} {}", base, gain);
+    }
+    if let Some(channel) = texture_map.channel {
+        let channel = match channel {
+            TextureChannel::Red => "r",
+            TextureChannel::Green => "g",
+            TextureChannel::Blue => "b",
+            TextureChannel::Matte => "m",
+            TextureChannel::Luminance => "l",
+            TextureChannel::Depth => "z",
+        };
+        options += &format!(" -imfchan {}", channel);
+    }
+    if let Some(resolution) = texture_map.resolution {
+        options += &format!(" -texres {}", resolution);
+    }
+    if let Some(map_type) = &texture_map.map_type {
+        options += &format!(" -type {}", map_type);
+    }
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MemoryResolver, Parser};
+
+    use std::{io::Cursor, path::Path};
+
+    #[test]
+    fn written_obj_is_parsed_into_same_data() {
+        let source = "\
+vp 0.5 0.25 2
+v 0 0 0 2 1 0 0
+v 1 0 0 1 0 1 0
+v 1 1 0 1 0 0 1
+v 0 1 0 0.5 1 1 1
+vt 0 0 0.5
+vt 1 1
+vn 0 0 1
+o quad
+g a b
+s 1
+f 1/1/1 2/2/1 3/1/1 4/2/1
+l 1 3
+p 2 4
+";
+        let mut parser = Parser::new(|_, _: &()| Ok(Cursor::new(vec![])));
+        let obj = parser.parse(source.as_bytes(), ()).unwrap();
+        let mut written = vec![];
+        obj.write_obj(&mut written, None).unwrap();
+        let reparsed = parser.parse(&written[..], ()).unwrap();
+        assert_eq!(format!("{:?}", reparsed), format!("{:?}", obj));
+    }
+
+    #[test]
+    fn written_mtl_is_parsed_into_same_materials() {
+        let mtl = "\
+newmtl metal  plate
+Ka 0.1 0.2 0.3
+Kd spectral curve.rfl 0.5
+Ks xyz 0.5 0.5 0.25
+Ns 10
+d -halo 0.5
+illum 2
+Pr 0.25
+map_Kd -o 0.5 0.5 -clamp on -mm 0.1 2 -imfchan r my  texture.png
+bump -bm 0.2 -type sphere bump.png
+newmtl empty
+";
+        let parse = |mtl: Vec<u8>| {
+            let mut parser = Parser::new(move |_, _: &()| Ok(Cursor::new(mtl.clone())));
+            parser.parse(&b"mtllib a.mtl\n"[..], ()).unwrap()
+        };
+        let obj = parse(mtl.into());
+        assert_eq!(obj.materials().len(), 2);
+        assert!(obj.diagnostics().is_empty());
+
+        let mut written = vec![];
+        obj.write_mtl(&mut written).unwrap();
+        assert!(String::from_utf8_lossy(&written).contains("\nd -halo 0.5\n"));
+        let reparsed = parse(written);
+        assert_eq!(reparsed.materials(), obj.materials());
+        let texture_map = reparsed.materials()[0].diffuse_map().unwrap();
+        assert_eq!(texture_map.path(), Path::new("my  texture.png"));
+    }
+
+    #[test]
+    fn duplicate_material_names_are_renamed() {
+        let resolver = MemoryResolver::new()
+            .with("a.mtl", "newmtl red\nKd 1 0 0\n")
+            .with("b.mtl", "newmtl red\nKd 0 1 0\nnewmtl red.2\n");
+        let mut parser = Parser::with_resolver(resolver);
+        let source = "mtllib a.mtl b.mtl\nv 0 0 0\nusemtl red\np 1\np 1\np 1\n";
+        let mut obj = parser.parse(source.as_bytes(), ()).unwrap();
+        let points = &mut obj.objects[0].groups[0].points;
+        points[1].1 = Some(1);
+        points[2].1 = Some(2);
+
+        let mut mtl = vec![];
+        obj.write_mtl(&mut mtl).unwrap();
+        let mut written = vec![];
+        obj.write_obj(&mut written, Some(Path::new("written.mtl")))
+            .unwrap();
+        let mut parser = Parser::new(move |_, _: &()| Ok(Cursor::new(mtl.clone())));
+        let reparsed = parser.parse(&written[..], ()).unwrap();
+
+        let names: Vec<_> = reparsed.materials().iter().map(|m| m.name()).collect();
+        assert_eq!(names, ["red", "red.2", "red.2.2"]);
+        let materials: Vec<_> = reparsed.objects()[0].groups()[0]
+            .points()
+            .map(|(_, material)| material)
+            .collect();
+        assert_eq!(materials, [Some(0), Some(1), Some(2)]);
+        for (reparsed, material) in reparsed.materials().iter().zip(obj.materials()) {
+            assert!(reparsed.properties().eq(material.properties()));
+        }
+    }
+}